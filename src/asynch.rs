@@ -0,0 +1,441 @@
+//! Async counterpart of `PCF8591`, generic over `embedded_hal_async::i2c::I2c`
+//!
+//! ```rust
+//! # #[cfg(feature = "mock")] {
+//! use futures_util::FutureExt;
+//! use pcf8591::{Address, Pin};
+//! use pcf8591::asynch::PCF8591;
+//! # use pcf8591::mock::MockPCF8591;
+//! # let i2c = MockPCF8591::new(0x48, 3.3);
+//! # i2c.set_input(Pin::AIN0, 1.2);
+//!
+//! let mut converter = PCF8591::from_i2c(i2c, Address::default(), 3.3);
+//! let read = async {
+//!     converter.analog_read(Pin::AIN0).await.unwrap()
+//! };
+//! // run by any executor, the simulated bus being always ready
+//! let v = read.now_or_never().unwrap();
+//! # assert!((v - 1.2).abs() < 0.02);
+//! # }
+//! ```
+
+use embedded_hal_async::i2c::{I2c, Operation};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(feature = "std")]
+use embedded_hal_async::delay::DelayNs;
+#[cfg(feature = "std")]
+use futures_util::stream::{self, Stream};
+
+#[cfg(feature = "std")]
+use crate::sampler::{Sample, Scan, Schedule};
+use crate::profile::Profile;
+use crate::selftest::{Report, SelfTest, Sweep};
+use crate::sensor::Conversion;
+use crate::state::{State, BURST_CHUNK, WRITE_CHUNK};
+use crate::{Address, Calibration, Channel, DiffPin, Error, InputMode, Oversampling, Pin, Result};
+
+/// A struct to handle PCF8591 converter over an async I2C bus
+///
+/// Exposes the same API as the blocking `pcf8591::PCF8591`, all bus accesses
+/// being `async`.
+pub struct PCF8591<I2C> {
+    i2c: I2C,
+    state: State,
+}
+
+impl<I2C: I2c> PCF8591<I2C> {
+
+    /// Creates a new converter over an existing async I2C bus
+    ///
+    /// - `i2c`: any async embedded-hal I2C bus
+    /// - `address`: as wired on A0, A1 and A2 pins (`0x48` per default)
+    /// - `v_ref`: is the board voltage (e.g. typically 3.3V on raspberry pi)
+    pub fn from_i2c(i2c: I2C, address: Address, v_ref: f64) -> PCF8591<I2C> {
+        PCF8591 {
+            i2c,
+            state: State::new(address, v_ref),
+        }
+    }
+
+    /// Creates a new PCF8591 over any async I2C bus, configured by a `Profile`
+    pub fn from_profile(i2c: I2C, profile: &Profile) -> PCF8591<I2C> {
+        PCF8591 {
+            i2c,
+            state: State::from_profile(profile),
+        }
+    }
+
+    /// Gets the current configuration, e.g. to save it after a calibration
+    pub fn profile(&self) -> Profile {
+        self.state.profile()
+    }
+
+    /// Sets the analog ground voltage (0V per default)
+    pub fn set_v_agnd(&mut self, v_agnd: f64) {
+        self.state.v_agnd = v_agnd;
+    }
+
+    /// Gets the reference voltage
+    pub fn v_ref(&self) -> f64 {
+        self.state.v_ref
+    }
+
+    /// Gets the analog ground voltage
+    pub fn v_agnd(&self) -> f64 {
+        self.state.v_agnd
+    }
+
+    /// Sets the input mode the board is wired for, see `InputMode::channels`
+    ///
+    /// It is only stored in the profile, each read selecting the mode of the
+    /// channel it reads.
+    pub fn set_input_mode(&mut self, mode: InputMode) {
+        self.state.input_mode = mode;
+    }
+
+    /// Gets the input mode the board is wired for
+    pub fn input_mode(&self) -> InputMode {
+        self.state.input_mode
+    }
+
+    /// Sets the calibration of an input pin, applied to its single-ended readings
+    pub fn set_calibration(&mut self, pin: Pin, calibration: Calibration) {
+        self.state.calibrations[pin as usize] = calibration;
+    }
+
+    /// Gets the calibration of an input pin
+    pub fn calibration(&self, pin: Pin) -> Calibration {
+        self.state.calibrations[pin as usize]
+    }
+
+    /// Sets the calibration of the analog output, applied to written voltages
+    pub fn set_output_calibration(&mut self, calibration: Calibration) {
+        self.state.output_calibration = calibration;
+    }
+
+    /// Gets the calibration of the analog output
+    pub fn output_calibration(&self) -> Calibration {
+        self.state.output_calibration
+    }
+
+    /// Converts a code into the nominal, non calibrated, pin voltage
+    pub fn code_to_voltage(&self, code: u8) -> f64 {
+        self.state.code_to_voltage(code)
+    }
+
+    /// Destroys the converter and gives back the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Reads a single byte from the converter
+    async fn read_byte(&mut self) -> Result<u8, I2C::Error> {
+        let mut buf = [0];
+        self.state.start_read();
+        self.i2c.read(self.state.address.value(), &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    /// Sends the control byte if needed then reads the last conversion
+    async fn read_control(&mut self, control_byte: u8) -> Result<u8, I2C::Error> {
+        if self.state.control != Some(control_byte) {
+            self.i2c.write(self.state.address.value(), &[control_byte]).await?;
+            self.read_byte().await?; // previous byte, unspecified
+            self.state.control = Some(control_byte);
+        }
+        self.read_byte().await
+    }
+
+    /// Reads successive conversions of one input pin in a single read transaction
+    pub async fn read_burst(&mut self, pin: Pin, buffer: &mut [u8]) -> Result<(), I2C::Error> {
+        let control_byte = self.state.read_control(pin.channel());
+        if self.state.control != Some(control_byte) {
+            self.i2c.write(self.state.address.value(), &[control_byte]).await?;
+            self.state.control = Some(control_byte);
+        }
+        let mut stale = [0];
+        self.state.start_read();
+        self.i2c.transaction(
+            self.state.address.value(),
+            &mut [Operation::Read(&mut stale), Operation::Read(buffer)],
+        ).await?;
+        Ok(())
+    }
+
+    /// Reads `samples` conversions of one input pin and sums them
+    async fn read_sum(&mut self, pin: Pin, samples: u32) -> Result<u32, I2C::Error> {
+        let mut buffer = [0; BURST_CHUNK];
+        let mut remaining = samples as usize;
+        let mut sum = 0;
+        while remaining > 0 {
+            let chunk = &mut buffer[..remaining.min(BURST_CHUNK)];
+            self.read_burst(pin, chunk).await?;
+            sum += chunk.iter().map(|&b| b as u32).sum::<u32>();
+            remaining -= chunk.len();
+        }
+        Ok(sum)
+    }
+
+    /// Sets the oversampling of an input pin, applied by every reading of the pin alone
+    pub fn set_oversampling(&mut self, pin: Pin, oversampling: Oversampling) {
+        self.state.oversampling[pin as usize] = oversampling;
+    }
+
+    /// Gets the oversampling of an input pin
+    pub fn oversampling(&self, pin: Pin) -> Oversampling {
+        self.state.oversampling[pin as usize]
+    }
+
+    /// Reads several conversions of an input pin and combines them into a code
+    pub async fn read_oversampled(&mut self, pin: Pin, oversampling: Oversampling) -> Result<f64, I2C::Error> {
+        let sum = self.read_sum(pin, oversampling.samples()).await?;
+        Ok(oversampling.code(sum))
+    }
+
+    /// Reads `4^bits` conversions of an input pin and decimates them
+    pub async fn read_decimated(&mut self, pin: Pin, bits: u8) -> Result<u16, I2C::Error> {
+        let bits = bits.min(Oversampling::MAX_BITS);
+        let sum = self.read_sum(pin, Oversampling::Decimate(bits).samples()).await?;
+        Ok((sum >> bits) as u16)
+    }
+
+    /// Reads analog values out of input pin and output digital byte
+    pub async fn analog_read_byte(&mut self, pin: Pin) -> Result<u8, I2C::Error> {
+        let control_byte = self.state.read_control(pin.channel());
+        self.read_control(control_byte).await
+    }
+
+    /// Reads the difference between two analog inputs and output a signed digital byte
+    pub async fn analog_read_differential_byte(&mut self, pin: DiffPin) -> Result<i8, I2C::Error> {
+        let control_byte = self.state.read_control(pin.channel());
+        self.read_control(control_byte).await.map(|b| b as i8)
+    }
+
+    /// Reads analog values out of input pin and output corresponding input voltage
+    pub async fn analog_read(&mut self, pin: Pin) -> Result<f64, I2C::Error> {
+        Ok(self.read_pin(pin).await?.1)
+    }
+
+    /// Reads an input pin, oversampled if configured, as its code and calibrated voltage
+    async fn read_pin(&mut self, pin: Pin) -> Result<(u8, f64), I2C::Error> {
+        match self.state.oversampling[pin as usize] {
+            Oversampling::None => {
+                let b = self.analog_read_byte(pin).await?;
+                Ok((b, self.state.pin_voltage(pin, b)))
+            }
+            oversampling => {
+                let code = self.read_oversampled(pin, oversampling).await?;
+                Ok(self.state.oversampled_reading(pin, code))
+            }
+        }
+    }
+
+    /// Reads the difference between two analog inputs and output corresponding voltage
+    pub async fn analog_read_differential(&mut self, pin: DiffPin) -> Result<f64, I2C::Error> {
+        let b = self.analog_read_differential_byte(pin).await?;
+        Ok(self.state.diff_to_voltage(b))
+    }
+
+    /// Reads either a single-ended or a differential input and output corresponding voltage
+    pub async fn analog_read_channel(&mut self, channel: Channel) -> Result<f64, I2C::Error> {
+        match channel {
+            Channel::Single(pin) => self.analog_read(pin).await,
+            Channel::Differential(pin) => self.analog_read_differential(pin).await,
+        }
+    }
+
+    /// Reads an input pin and converts it into a physical quantity
+    pub async fn read_sensor<C: Conversion>(&mut self, pin: Pin, conversion: &C) -> Result<f64, I2C::Error> {
+        let (code, voltage) = self.read_pin(pin).await?;
+        Ok(conversion.convert(code, voltage))
+    }
+
+    /// Reads either a single-ended or a differential input as a timestamped `Sample`
+    #[cfg(feature = "std")]
+    pub async fn sample(&mut self, channel: Channel) -> Result<Sample, I2C::Error> {
+        if let Channel::Single(pin) = channel {
+            if self.state.oversampling[pin as usize] != Oversampling::None {
+                let (code, voltage) = self.read_pin(pin).await?;
+                let timestamp = self.state.converted_at.unwrap_or_else(Instant::now);
+                return Ok(Sample::new(channel, code, voltage, timestamp));
+            }
+        }
+        let control_byte = self.state.read_control(channel.channel());
+        let code = self.read_control(control_byte).await?;
+        let timestamp = self.state.acquired_at.unwrap_or_else(Instant::now);
+        Ok(Sample::new(channel, code, self.state.channel_voltage(channel, code), timestamp))
+    }
+
+    /// Reads all four input pins, in order, as timestamped `Sample`s
+    #[cfg(feature = "std")]
+    pub async fn sample_all(&mut self) -> Result<[Sample; 4], I2C::Error> {
+        let codes = self.read_all().await?;
+        let timestamp = self.state.converted_at.unwrap_or_else(Instant::now);
+        let pins = [Pin::AIN0, Pin::AIN1, Pin::AIN2, Pin::AIN3];
+        Ok(pins.map(|pin| {
+            let code = codes[pin as usize];
+            Sample::new(Channel::Single(pin), code, self.state.pin_voltage(pin, code), timestamp)
+        }))
+    }
+
+    /// Creates an endless stream reading `channels` at a fixed `rate`, in Hz
+    ///
+    /// Async counterpart of `pcf8591::sampler::Sampler`, waiting for the
+    /// deadlines with `delay`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not strictly positive and finite
+    #[cfg(feature = "std")]
+    pub fn stream<'a, D: DelayNs + 'a>(&'a mut self, channels: &[Channel], rate: f64, delay: D)
+        -> impl Stream<Item = Result<Scan, I2C::Error>> + 'a
+    {
+        let state = (self, channels.to_vec(), Schedule::new(rate), delay);
+        stream::unfold(state, |(converter, channels, mut schedule, mut delay)| async move {
+            if let Some(remaining) = schedule.remaining() {
+                delay.delay_us(remaining.as_micros().min(u32::MAX as u128) as u32).await;
+            }
+            let overruns = schedule.catch_up();
+            let mut samples = Vec::with_capacity(channels.len());
+            let mut scan = Ok(());
+            for channel in &channels {
+                match converter.sample(*channel).await {
+                    Ok(s) => samples.push(s),
+                    Err(e) => {
+                        scan = Err(e);
+                        break;
+                    }
+                }
+            }
+            let scan = scan.map(|_| Scan { samples, overruns });
+            schedule.advance();
+            Some((scan, (converter, channels, schedule, delay)))
+        })
+    }
+
+    /// Reads all four input pins, in order, as digital bytes, using auto-increment
+    pub async fn read_all(&mut self) -> Result<[u8; 4], I2C::Error> {
+        let control_byte = self.state.scan_control();
+        self.i2c.write(self.state.address.value(), &[control_byte]).await?;
+        self.state.control = Some(control_byte);
+        let mut buf = [0; 5];
+        self.state.start_read();
+        self.i2c.read(self.state.address.value(), &mut buf).await?;
+        Ok([buf[1], buf[2], buf[3], buf[4]])
+    }
+
+    /// Reads all four input pins, in order, and output corresponding input voltages
+    pub async fn analog_read_all(&mut self) -> Result<[f64; 4], I2C::Error> {
+        let bytes = self.read_all().await?;
+        Ok(self.state.scan_voltages(bytes))
+    }
+
+    /// Enables the analog output
+    pub async fn enable_output(&mut self) -> Result<(), I2C::Error> {
+        self.set_output(true).await
+    }
+
+    /// Disables the analog output
+    pub async fn disable_output(&mut self) -> Result<(), I2C::Error> {
+        self.set_output(false).await
+    }
+
+    /// Returns true if the analog output is enabled
+    pub fn is_output_enabled(&self) -> bool {
+        self.state.output_enabled
+    }
+
+    /// Sends a control byte with the new output state
+    async fn set_output(&mut self, enabled: bool) -> Result<(), I2C::Error> {
+        let control_byte = self.state.output_control(enabled);
+        self.state.control = None;
+        self.i2c.write(self.state.address.value(), &[control_byte]).await?;
+        self.state.output_enabled = enabled;
+        Ok(())
+    }
+
+    /// Writes analog values, as byte, in the output pin
+    pub async fn analog_write_byte(&mut self, value: u8) -> Result<(), I2C::Error> {
+        self.state.control = None;
+        let control_byte = self.state.write_control();
+        self.i2c.write(self.state.address.value(), &[control_byte, value]).await?;
+        self.state.dac = value;
+        Ok(())
+    }
+
+    /// Writes successive DAC values in a single write transaction
+    pub async fn write_samples(&mut self, values: &[u8]) -> Result<(), I2C::Error> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        self.state.control = None;
+        let control_byte = self.state.write_control();
+        self.i2c.transaction(
+            self.state.address.value(),
+            &mut [Operation::Write(&[control_byte]), Operation::Write(values)],
+        ).await?;
+        self.state.dac = values[values.len() - 1];
+        Ok(())
+    }
+
+    /// Writes successive DAC values, `chunk` values per write transaction
+    pub async fn write_samples_chunked(&mut self, values: &[u8], chunk: usize) -> Result<(), I2C::Error> {
+        for c in values.chunks(chunk) {
+            self.write_samples(c).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes successive voltages in the output pin, rejecting out of range voltages
+    pub async fn analog_write_samples(&mut self, voltages: &[f64]) -> Result<(), I2C::Error> {
+        self.state.check_voltages(voltages)?;
+        let mut codes = [0; WRITE_CHUNK];
+        for chunk in voltages.chunks(WRITE_CHUNK) {
+            for (code, &v) in codes.iter_mut().zip(chunk) {
+                *code = self.state.voltage_to_code(v)?;
+            }
+            self.write_samples(&codes[..chunk.len()]).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes analog values in the output pin, rejecting out of range voltages
+    pub async fn analog_write(&mut self, v_out: f64) -> Result<(), I2C::Error> {
+        let code = self.state.voltage_to_code(v_out)?;
+        self.analog_write_byte(code).await
+    }
+
+    /// Writes analog values in the output pin, saturating out of range voltages
+    pub async fn analog_write_saturating(&mut self, v_out: f64) -> Result<(), I2C::Error> {
+        let code = self.state.voltage_to_code_saturating(v_out)?;
+        self.analog_write_byte(code).await
+    }
+
+    /// Sweeps the DAC and reads it back on an input pin wired to AOUT
+    pub async fn self_test(&mut self, test: &SelfTest) -> Result<Report, I2C::Error> {
+        let (enabled, dac) = (self.state.output_enabled, self.state.dac);
+        if !enabled {
+            self.enable_output().await?;
+        }
+        let mut sweep = Sweep::new();
+        let mut buffer = [0; 255];
+        let readings = &mut buffer[..test.samples.max(1) as usize];
+        for code in 0..=255 {
+            self.analog_write_byte(code).await?;
+            self.read_burst(test.pin, readings).await?;
+            sweep.record(code, readings);
+        }
+        self.analog_write_byte(dac).await?;
+        if !enabled {
+            self.disable_output().await?;
+        }
+        let report = sweep.report();
+        if report.passes(&test.tolerances) {
+            Ok(report)
+        } else {
+            Err(Error::SelfTestFailed(report))
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Command-line access to a PCF8591 converter
+//!
+//! Prints one line per reading, values separated by tabs, so the output can
+//! be piped to other tools. Errors are printed on stderr with a non-zero exit
+//! status.
+
+use std::convert::TryFrom;
+use std::env;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use pcf8591::profile::Profile;
+use pcf8591::sampler::Sample;
+use pcf8591::{Address, Channel, DiffPin, InputMode, LinuxBus, LinuxBusError, Pin, PCF8591};
+
+const USAGE: &str = "\
+Usage: pcf8591 [OPTIONS] <COMMAND>
+
+Commands:
+  read <CHANNEL>        Read one input: 0 to 3, or a differential pair 0-3, 1-3, 2-3, 0-1
+  read-all              Read all inputs of the input mode
+  write <VOLTS>         Set the analog output, or its code with --raw
+  off                   Disable the analog output, until a command without --output off
+  scan                  List the devices answering at 0x48 to 0x4F, without
+                        writing to them; other chips in that range are listed too
+  stream <RATE> [N]     Read all inputs of the input mode RATE times per second,
+                        N times or until interrupted
+
+Options:
+  -d, --device <PATH>     I2C bus [default: /dev/i2c-1]
+  -a, --address <ADDR>    Address, 0x48 to 0x4F, or device number, 0 to 7 [default: 0x48]
+  -r, --v-ref <VOLTS>     Reference voltage [default: 3.3]
+  -p, --profile <FILE>    Load address, voltages and calibrations from a TOML profile
+  -m, --mode <MODE>       Input mode: single, three-diff, mixed or two-diff [default: single]
+  -o, --output <STATE>    Analog output while running the command: on or off [default: on]
+      --raw               Read and write codes instead of volts
+  -h, --help              Print this help
+  -V, --version           Print the version
+";
+
+/// Parsed command line
+#[derive(Debug, Clone, PartialEq)]
+struct Args {
+    device: String,
+    address: Option<Address>,
+    v_ref: Option<f64>,
+    profile: Option<String>,
+    mode: Option<InputMode>,
+    output: bool,
+    raw: bool,
+    command: Command,
+}
+
+/// Action to run
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Help,
+    Version,
+    Read(Channel),
+    ReadAll,
+    Write(Output),
+    Off,
+    Scan,
+    Stream { rate: f64, count: Option<u64> },
+}
+
+/// Value to set on the analog output
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Volts(f64),
+    Code(u8),
+}
+
+fn parse_address(s: &str) -> Result<Address, String> {
+    let value = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    value
+        .ok()
+        .and_then(|v| Address::new(v).or_else(|| u8::try_from(v).ok().and_then(Address::from_device)))
+        .ok_or_else(|| format!("invalid address '{}'", s))
+}
+
+fn parse_channel(s: &str) -> Result<Channel, String> {
+    let channel = match s {
+        "0" => Pin::AIN0.into(),
+        "1" => Pin::AIN1.into(),
+        "2" => Pin::AIN2.into(),
+        "3" => Pin::AIN3.into(),
+        "0-3" => DiffPin::AIN0_AIN3.into(),
+        "1-3" => DiffPin::AIN1_AIN3.into(),
+        "2-3" => DiffPin::AIN2_AIN3.into(),
+        "0-1" => DiffPin::AIN0_AIN1.into(),
+        _ => return Err(format!("invalid channel '{}'", s)),
+    };
+    Ok(channel)
+}
+
+fn parse_mode(s: &str) -> Result<InputMode, String> {
+    match s {
+        "single" => Ok(InputMode::SingleEnded),
+        "three-diff" => Ok(InputMode::ThreeDifferential),
+        "mixed" => Ok(InputMode::Mixed),
+        "two-diff" => Ok(InputMode::TwoDifferential),
+        _ => Err(format!("invalid input mode '{}'", s)),
+    }
+}
+
+fn parse_output(s: &str) -> Result<bool, String> {
+    match s {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("invalid output state '{}'", s)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(s: &str, what: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid {} '{}'", what, s))
+}
+
+fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+    let mut device = "/dev/i2c-1".to_string();
+    let (mut address, mut v_ref, mut profile, mut mode) = (None, None, None, None);
+    let (mut output, mut raw) = (true, false);
+    let mut positionals = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value of '{}'", arg));
+        match arg.as_str() {
+            "-h" | "--help" => positionals.insert(0, "help".to_string()),
+            "-V" | "--version" => positionals.insert(0, "version".to_string()),
+            "-d" | "--device" => device = value()?,
+            "-a" | "--address" => address = Some(parse_address(&value()?)?),
+            "-r" | "--v-ref" => v_ref = Some(parse_number(&value()?, "voltage")?),
+            "-p" | "--profile" => profile = Some(value()?),
+            "-m" | "--mode" => mode = Some(parse_mode(&value()?)?),
+            "-o" | "--output" => output = parse_output(&value()?)?,
+            "--raw" => raw = true,
+            // negative voltages are values, not options
+            a if a.starts_with('-') && a.parse::<f64>().is_err() => {
+                return Err(format!("unknown option '{}'", a));
+            }
+            _ => positionals.push(arg),
+        }
+    }
+
+    let mut positionals = positionals.iter().map(String::as_str);
+    let mut operand = |what: &str| positionals.next().ok_or_else(|| format!("missing {}", what));
+    let command = match operand("command")? {
+        "help" => Command::Help,
+        "version" => Command::Version,
+        "read" => Command::Read(parse_channel(operand("channel")?)?),
+        "read-all" => Command::ReadAll,
+        "write" if raw => Command::Write(Output::Code(parse_number(operand("code")?, "code")?)),
+        "write" => Command::Write(Output::Volts(parse_number(operand("voltage")?, "voltage")?)),
+        "off" => Command::Off,
+        "scan" => Command::Scan,
+        "stream" => {
+            let rate = parse_number(operand("rate")?, "rate")?;
+            if !(rate > 0. && f64::is_finite(rate)) {
+                return Err(format!("invalid rate '{}'", rate));
+            }
+            let count = match positionals.next() {
+                Some(n) => Some(parse_number(n, "count")?),
+                None => None,
+            };
+            Command::Stream { rate, count }
+        }
+        c => return Err(format!("unknown command '{}'", c)),
+    };
+    if let Some(extra) = positionals.next() {
+        if command != Command::Help && command != Command::Version {
+            return Err(format!("unexpected argument '{}'", extra));
+        }
+    }
+
+    Ok(Args { device, address, v_ref, profile, mode, output, raw, command })
+}
+
+/// Formats a sample as a code, signed for differential inputs, or as volts
+fn format_sample(sample: &Sample, raw: bool) -> String {
+    match (raw, sample.channel) {
+        (false, _) => format!("{:.3}", sample.voltage),
+        (true, Channel::Single(_)) => sample.code.to_string(),
+        (true, Channel::Differential(_)) => (sample.code as i8).to_string(),
+    }
+}
+
+/// Prints a line of tab separated values
+fn print_line<T: Display>(out: &mut impl Write, values: &[T]) -> io::Result<()> {
+    let line = values.iter().map(T::to_string).collect::<Vec<_>>().join("\t");
+    writeln!(out, "{}", line)
+}
+
+/// Error of a command, printed on stderr
+type CommandResult<T = ()> = Result<T, Box<dyn Error>>;
+
+/// Opens the converter configured by the profile and the options
+fn open(args: &Args) -> CommandResult<(PCF8591<LinuxBus>, InputMode)> {
+    let mut profile = match args.profile {
+        Some(ref path) => Profile::load_toml(path).map_err(|e| format!("{}: {}", path, e))?,
+        None => Profile::new(3.3),
+    };
+    if let Some(address) = args.address {
+        profile.address = address;
+    }
+    if let Some(v_ref) = args.v_ref {
+        profile.v_ref = v_ref;
+    }
+    let mut converter = PCF8591::open(&args.device, &profile)?;
+    // reads send the output enable flag, a disabled output is disabled again
+    if !args.output {
+        converter.disable_output()?;
+    }
+    Ok((converter, args.mode.unwrap_or(profile.input_mode)))
+}
+
+fn run(args: &Args, out: &mut impl Write) -> CommandResult {
+    match args.command {
+        Command::Help => out.write_all(USAGE.as_bytes())?,
+        Command::Version => writeln!(out, "pcf8591 {}", env!("CARGO_PKG_VERSION"))?,
+        Command::Scan => {
+            for address in pcf8591::probe(&args.device)? {
+                writeln!(out, "{:#04x}", address.value())?;
+            }
+        }
+        Command::Read(channel) => {
+            let (mut converter, _) = open(args)?;
+            let sample = converter.sample(channel)?;
+            print_line(out, &[format_sample(&sample, args.raw)])?;
+        }
+        Command::ReadAll => {
+            let (mut converter, mode) = open(args)?;
+            let samples = mode
+                .channels()
+                .iter()
+                .map(|&c| converter.sample(c).map(|s| format_sample(&s, args.raw)))
+                .collect::<Result<Vec<_>, _>>()?;
+            print_line(out, &samples)?;
+        }
+        Command::Write(Output::Volts(v)) => open(args)?.0.analog_write(v)?,
+        Command::Write(Output::Code(code)) => open(args)?.0.analog_write_byte(code)?,
+        Command::Off => open(args)?.0.disable_output()?,
+        Command::Stream { rate, count } => {
+            let (mut converter, mode) = open(args)?;
+            let scans = converter.sampler(mode.channels(), rate);
+            for scan in scans.take(count.map_or(usize::MAX, |n| n as usize)) {
+                let scan = scan?;
+                if scan.overruns > 0 {
+                    eprintln!("warning: {} scans skipped, the bus cannot keep up", scan.overruns);
+                }
+                let samples = scan.samples.iter().map(|s| format_sample(s, args.raw)).collect::<Vec<_>>();
+                print_line(out, &samples)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Describes an error for the user, bus errors with their own message rather than their debug form
+fn describe(e: &(dyn Error + 'static)) -> String {
+    match e.downcast_ref::<pcf8591::Error<LinuxBusError>>() {
+        Some(pcf8591::Error::I2c(e)) => format!("I2C bus error: {}", e),
+        _ => e.to_string(),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\nSee 'pcf8591 --help' for usage.", e);
+            return ExitCode::from(2);
+        }
+    };
+    let stdout = io::stdout();
+    match run(&args, &mut stdout.lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        // the reader of the output, e.g. `head`, exited
+        Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) => {
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", describe(e.as_ref()));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Args, String> {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(args("read 2").unwrap().command, Command::Read(Pin::AIN2.into()));
+        assert_eq!(args("read 0-1").unwrap().command, Command::Read(DiffPin::AIN0_AIN1.into()));
+        assert_eq!(args("write -0.5").unwrap().command, Command::Write(Output::Volts(-0.5)));
+        assert_eq!(args("--raw write 200").unwrap().command, Command::Write(Output::Code(200)));
+        assert_eq!(
+            args("stream 100 5").unwrap().command,
+            Command::Stream { rate: 100., count: Some(5) }
+        );
+        assert_eq!(args("stream 10").unwrap().command, Command::Stream { rate: 10., count: None });
+        assert_eq!(args("scan --help").unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn options() {
+        let a = args("-d /dev/i2c-0 -a 0x4a read-all -r 5 -m mixed --raw").unwrap();
+        assert_eq!(a.device, "/dev/i2c-0");
+        assert_eq!(a.address, Address::new(0x4A));
+        assert_eq!(a.v_ref, Some(5.));
+        assert_eq!(a.mode, Some(InputMode::Mixed));
+        assert!(a.raw);
+        assert!(a.output);
+        assert!(!args("-o off read 0").unwrap().output);
+        assert!(args("--output on read 0").unwrap().output);
+        assert_eq!(args("-a 3 scan").unwrap().address, Address::from_device(3));
+        assert_eq!(args("-a 75 scan").unwrap().address, Address::new(0x4B));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(args("").is_err());
+        assert!(args("read 4").is_err());
+        assert!(args("read").is_err());
+        assert!(args("--raw write 256").is_err());
+        assert!(args("stream 0").is_err());
+        assert!(args("-a 0x50 scan").is_err());
+        assert!(args("-m quad read-all").is_err());
+        assert!(args("--verbose scan").is_err());
+        assert!(args("scan now").is_err());
+        assert!(args("read 1 -d").is_err());
+        assert!(args("--output none read 0").is_err());
+    }
+
+    #[test]
+    fn error_messages() {
+        let e = PCF8591::new("/dev/i2c-nope", Address::default(), 3.3).err().unwrap();
+        assert_eq!(describe(&e), "I2C bus error: No such file or directory (os error 2)");
+        let e: Box<dyn Error> = "invalid profile".into();
+        assert_eq!(describe(e.as_ref()), "invalid profile");
+    }
+}
@@ -0,0 +1,237 @@
+//! Several converters sharing one I2C bus
+//!
+//! Up to eight PCF8591 can be chained on a bus, each one being identified by
+//! the level of its three hardware address pins A0, A1 and A2 (Table 5). The
+//! `Bus` owns the I2C handle and exposes the 32 inputs as a flat namespace.
+//!
+//! ```rust,should_panic
+//! # #[cfg(feature = "std")] {
+//! use pcf8591::{Address, Pin};
+//! use pcf8591::bus::{Bus, BusPin};
+//!
+//! let mut bus = Bus::open("/dev/i2c-1").unwrap();
+//! bus.add(Address::default(), 3.3);
+//! bus.add(Address::from_pins(true, true, false), 3.3);
+//!
+//! // device 3 (A0 and A1 high), input AIN2
+//! let v = bus.analog_read(BusPin::new(3, Pin::AIN2)).unwrap();
+//!
+//! // any converter API is available per device
+//! bus.device(0).unwrap().analog_write(1.2).unwrap();
+//!
+//! // all inputs of all devices, `None` for missing devices
+//! let all = bus.analog_read_all().unwrap();
+//! # }
+//! # #[cfg(not(feature = "std"))] panic!();
+//! ```
+
+use core::ops::{Deref, DerefMut};
+
+use embedded_hal::i2c::I2c;
+
+use crate::state::State;
+use crate::{Address, Error, Pin, Result, PCF8591};
+#[cfg(feature = "std")]
+use crate::{LinuxBus, LinuxBusError};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+const PINS: [Pin; 4] = [Pin::AIN0, Pin::AIN1, Pin::AIN2, Pin::AIN3];
+
+/// Checks whether a device answers at `address`
+///
+/// Reads one byte, which leaves the control register and the DAC register of
+/// a PCF8591 untouched, so a converter driving its analog output keeps doing
+/// so. Any device acknowledging the address is reported, including other
+/// chips of the `0x48` to `0x4F` range such as LM75 or TMP102 temperature
+/// sensors.
+pub fn detect<I2C: I2c>(i2c: &mut I2C, address: Address) -> bool {
+    let mut buf = [0];
+    i2c.read(address.value(), &mut buf).is_ok()
+}
+
+/// An input pin of one of the converters of the bus
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusPin {
+    /// Device number, i.e. `A2 A1 A0` address pins as a 3 bits number
+    pub device: u8,
+    /// Input pin of the device
+    pub pin: Pin,
+}
+
+impl BusPin {
+    /// Creates a new bus pin
+    pub fn new(device: u8, pin: Pin) -> BusPin {
+        BusPin { device, pin }
+    }
+
+    /// Index in the flat namespace, `device * 4 + pin`, in `0..32`
+    pub fn index(&self) -> usize {
+        self.device as usize * 4 + self.pin as usize
+    }
+
+    /// Gets the bus pin from its index in the flat namespace
+    pub fn from_index(index: usize) -> Option<BusPin> {
+        if index < 32 {
+            Some(BusPin::new((index / 4) as u8, PINS[index % 4]))
+        } else {
+            None
+        }
+    }
+}
+
+/// Up to eight converters sharing one I2C bus
+pub struct Bus<I2C> {
+    i2c: I2C,
+    devices: [Option<State>; 8],
+}
+
+#[cfg(feature = "std")]
+impl Bus<LinuxBus> {
+    /// Opens the i2c bus at given path (e.g. `/dev/i2c-1`), without any device
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Bus<LinuxBus>> {
+        let i2c = LinuxBus::new(path).map_err(|e| Error::I2c(LinuxBusError(e)))?;
+        Ok(Bus::new(i2c))
+    }
+}
+
+impl<I2C: I2c> Bus<I2C> {
+    /// Creates a new bus, without any device
+    pub fn new(i2c: I2C) -> Bus<I2C> {
+        Bus {
+            i2c,
+            devices: Default::default(),
+        }
+    }
+
+    /// Destroys the bus and gives back the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Adds the converter wired at given address, replacing any previous one
+    ///
+    /// - `address`: as wired on A0, A1 and A2 pins
+    /// - `v_ref`: is the board voltage of this converter
+    pub fn add(&mut self, address: Address, v_ref: f64) {
+        self.devices[address.device() as usize] = Some(State::new(address, v_ref));
+    }
+
+    /// Detects the converters answering on the bus (see `detect`) and adds them
+    ///
+    /// Devices already added keep their configuration, their next access
+    /// sending a full control byte again. Returns the number of devices
+    /// answering.
+    pub fn discover(&mut self, v_ref: f64) -> usize {
+        let mut found = 0;
+        for address in (0..8).filter_map(Address::from_device) {
+            if detect(&mut self.i2c, address) {
+                match self.devices[address.device() as usize] {
+                    Some(ref mut state) => state.control = None,
+                    None => self.add(address, v_ref),
+                }
+                found += 1;
+            }
+        }
+        found
+    }
+
+    /// Removes a converter from the bus, returns true if it was present
+    pub fn remove(&mut self, device: u8) -> bool {
+        self.devices
+            .get_mut(device as usize)
+            .and_then(|d| d.take())
+            .is_some()
+    }
+
+    /// Iterates over the numbers of the added devices
+    pub fn devices(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..8u8).filter(move |&d| self.devices[d as usize].is_some())
+    }
+
+    /// Gets one converter, with its full API, or `None` if it was not added
+    pub fn device(&mut self, device: u8) -> Option<Device<'_, I2C>> {
+        let slot = self.devices.get_mut(device as usize)?;
+        let state = slot.take()?;
+        Some(Device {
+            slot,
+            converter: Some(PCF8591 { i2c: &mut self.i2c, state }),
+        })
+    }
+
+    fn device_or_err(&mut self, device: u8) -> Result<Device<'_, I2C>, I2C::Error> {
+        self.device(device).ok_or(Error::DeviceNotFound(device))
+    }
+
+    /// Reads an input pin of one of the converters as digital byte
+    pub fn analog_read_byte(&mut self, pin: BusPin) -> Result<u8, I2C::Error> {
+        self.device_or_err(pin.device)?.analog_read_byte(pin.pin)
+    }
+
+    /// Reads an input pin of one of the converters and output corresponding voltage
+    pub fn analog_read(&mut self, pin: BusPin) -> Result<f64, I2C::Error> {
+        self.device_or_err(pin.device)?.analog_read(pin.pin)
+    }
+
+    /// Reads all inputs of all converters as digital bytes, indexed by `BusPin::index`
+    ///
+    /// Uses one auto-increment scan per device, inputs of missing devices are `None`.
+    pub fn read_all(&mut self) -> Result<[Option<u8>; 32], I2C::Error> {
+        let mut values = [None; 32];
+        for device in 0..8 {
+            if let Some(mut converter) = self.device(device) {
+                let bytes = converter.read_all()?;
+                for (v, b) in values[device as usize * 4..].iter_mut().zip(bytes.iter()) {
+                    *v = Some(*b);
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Reads all inputs of all converters as voltages, indexed by `BusPin::index`
+    ///
+    /// Uses one auto-increment scan per device, inputs of missing devices are `None`.
+    pub fn analog_read_all(&mut self) -> Result<[Option<f64>; 32], I2C::Error> {
+        let mut values = [None; 32];
+        for device in 0..8 {
+            if let Some(mut converter) = self.device(device) {
+                let voltages = converter.analog_read_all()?;
+                for (v, b) in values[device as usize * 4..].iter_mut().zip(voltages.iter()) {
+                    *v = Some(*b);
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// One converter of a `Bus`, borrowing the shared I2C bus
+///
+/// Dereferences to `PCF8591`, its state being stored back in the bus when dropped.
+pub struct Device<'a, I2C> {
+    slot: &'a mut Option<State>,
+    converter: Option<PCF8591<&'a mut I2C>>,
+}
+
+impl<'a, I2C> Deref for Device<'a, I2C> {
+    type Target = PCF8591<&'a mut I2C>;
+
+    fn deref(&self) -> &Self::Target {
+        self.converter.as_ref().expect("converter is only taken on drop")
+    }
+}
+
+impl<'a, I2C> DerefMut for Device<'a, I2C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.converter.as_mut().expect("converter is only taken on drop")
+    }
+}
+
+impl<'a, I2C> Drop for Device<'a, I2C> {
+    fn drop(&mut self) {
+        if let Some(converter) = self.converter.take() {
+            *self.slot = Some(converter.state);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Linear correction of the front-end circuitry of inputs and output
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Linear correction between a pin voltage and the actual signal voltage
+///
+/// The signal voltage is `divider * (gain * v_pin + offset)`, where `v_pin`
+/// is the nominal voltage of the code on the converter pin. `divider` is the
+/// ratio of a resistor divider (e.g. `2.` when halving the signal), `gain`
+/// and `offset` correct the buffer and the converter errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Calibration {
+    /// Offset added after the gain, in volts at the pin
+    pub offset: f64,
+    /// Gain applied to the pin voltage
+    pub gain: f64,
+    /// Resistor divider ratio, signal voltage over pin voltage
+    pub divider: f64,
+}
+
+impl Default for Calibration {
+    /// Identity calibration
+    fn default() -> Calibration {
+        Calibration {
+            offset: 0.,
+            gain: 1.,
+            divider: 1.,
+        }
+    }
+}
+
+impl Calibration {
+    /// Calibration of an ideal resistor divider, signal voltage over pin voltage
+    pub fn divider(divider: f64) -> Calibration {
+        Calibration {
+            divider,
+            ..Calibration::default()
+        }
+    }
+
+    /// Computes the calibration from two points, each one being the nominal pin
+    /// voltage (see `PCF8591::code_to_voltage`) and the actual signal voltage
+    ///
+    /// The divider ratio is kept, `None` is returned if both pin voltages are
+    /// equal or the result is not finite.
+    pub fn two_point(self, low: (f64, f64), high: (f64, f64)) -> Option<Calibration> {
+        let (pin_low, actual_low) = (low.0, low.1 / self.divider);
+        let (pin_high, actual_high) = (high.0, high.1 / self.divider);
+        let gain = (actual_high - actual_low) / (pin_high - pin_low);
+        let offset = actual_low - gain * pin_low;
+        if gain.is_finite() && offset.is_finite() && gain != 0. {
+            Some(Calibration { offset, gain, ..self })
+        } else {
+            None
+        }
+    }
+
+    /// Converts a pin voltage into the signal voltage
+    pub fn apply(&self, v_pin: f64) -> f64 {
+        self.divider * (self.gain * v_pin + self.offset)
+    }
+
+    /// Converts a signal voltage into the pin voltage
+    pub fn invert(&self, v: f64) -> f64 {
+        (v / self.divider - self.offset) / self.gain
+    }
+}
@@ -0,0 +1,46 @@
+//! Error type returned by the converter
+
+use core::fmt;
+
+use crate::selftest::Report;
+
+/// Errors returned by the PCF8591 driver
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error<E> {
+    /// Error of the underlying I2C bus
+    I2c(E),
+    /// Voltage is NaN or cannot be output by the DAC
+    VoltageOutOfRange(f64),
+    /// No converter was added to the bus with this device number
+    DeviceNotFound(u8),
+    /// Self-test report out of the configured tolerances
+    SelfTestFailed(Report),
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Error::I2c(e)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::I2c(ref e) => write!(f, "I2C bus error: {:?}", e),
+            Error::VoltageOutOfRange(v) => write!(f, "voltage out of DAC range: {}V", v),
+            Error::DeviceNotFound(d) => write!(f, "no PCF8591 device {} on the bus", d),
+            Error::SelfTestFailed(ref r) => write!(f, "self-test out of tolerances: {}", r),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: ::std::error::Error + 'static> ::std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+        match *self {
+            Error::I2c(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -5,12 +5,13 @@
 //! # Examples
 //! 
 //! ```rust,should_panic
-//! use pcf8591::{PCF8591, Pin};
+//! # #[cfg(feature = "std")] {
+//! use pcf8591::{Address, PCF8591, Pin};
 //! use std::thread;
 //! use std::time::Duration;
 //!
-//! // Gets default location on raspberry pi (rev 2)
-//! let mut converter = PCF8591::new("/dev/i2c-1", 0x48, 3.3).unwrap();
+//! // Gets default location on raspberry pi (rev 2), all address pins low
+//! let mut converter = PCF8591::new("/dev/i2c-1", Address::default(), 3.3).unwrap();
 //!
 //! loop {
 //!     let v = converter.analog_read(Pin::AIN0).unwrap();
@@ -18,27 +19,135 @@
 //!
 //!     thread::sleep(Duration::from_millis(1000));
 //! }
+//! # }
+//! # #[cfg(not(feature = "std"))] panic!();
 //! ```
+//!
+//! The converter can also be driven by any `embedded_hal::i2c::I2c` implementation
+//! (microcontroller HAL, shared bus manager, test double...) using `PCF8591::from_i2c`.
+//!
+//! # Features
+//!
+//! - `std` (default): enables the linux backend, `PCF8591::new` and the
+//!   fixed-rate `sampler` and `waveform` modules. Disable it to use the crate
+//!   in `#![no_std]` firmwares.
+//! - `async`: enables the `asynch` module, an async driver generic over
+//!   `embedded_hal_async::i2c::I2c`.
+//! - `mock`: enables the `mock` module, a simulated PCF8591 to test code
+//!   without the physical chip.
+//! - `serde`: implements serde traits for the `profile::Profile` of a converter.
+//! - `toml`, `json`: add helpers to load and save profiles as TOML or JSON files.
+//! - `cli`: builds the `pcf8591` command-line tool, reading, writing and
+//!   scanning converters from shell scripts (`pcf8591 --help`).
 
 #![deny(missing_docs)]
-extern crate i2cdev;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod bus;
+mod calibration;
+mod error;
+mod oversampling;
+#[cfg(feature = "std")]
+pub mod sampler;
+pub mod selftest;
+pub mod sensor;
+mod state;
+#[cfg(feature = "std")]
+pub mod waveform;
+#[cfg(feature = "std")]
+mod linux;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod profile;
 
+#[cfg(feature = "std")]
 use std::path::Path;
-use i2cdev::linux::LinuxI2CDevice;
-use i2cdev::core::I2CDevice;
+#[cfg(feature = "std")]
+use std::time::Instant;
+use embedded_hal::i2c::{I2c, Operation};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "std")]
+use crate::sampler::{Sample, Sampler};
+use crate::profile::Profile;
+use crate::selftest::{Report, SelfTest, Sweep};
+use crate::sensor::Conversion;
+use crate::state::{State, BURST_CHUNK, WRITE_CHUNK};
+
+pub use crate::calibration::Calibration;
+pub use crate::error::Error;
+pub use crate::oversampling::Oversampling;
+#[cfg(feature = "std")]
 pub use i2cdev::linux::LinuxI2CError;
+#[cfg(feature = "std")]
+pub use crate::linux::{LinuxBus, LinuxBusError};
+
+/// Wrapper over `Error`, the bus error defaulting to the linux backend one
+#[cfg(feature = "std")]
+pub type Result<T, E = LinuxBusError> = ::std::result::Result<T, Error<E>>;
 
-/// Wrapper over LinuxI2CError
-pub type Result<T> = ::std::result::Result<T, LinuxI2CError>;
+/// Wrapper over `Error`, generic over the error of the underlying I2C bus
+#[cfg(not(feature = "std"))]
+pub type Result<T, E> = ::core::result::Result<T, Error<E>>;
 
 /// A struct to handle PCF8591 converter
 ///
 /// Allow user to read from given input pin and write to output pin
-pub struct PCF8591 {
-    i2c: LinuxI2CDevice,
-    pin: Option<Pin>,
-    v_lsb: f64,
+pub struct PCF8591<I2C> {
+    i2c: I2C,
+    state: State,
+}
+
+/// A PCF8591 slave address, as per Table 5
+///
+/// Only the three lowest bits are programmable, through the A0, A1 and A2
+/// hardware pins, giving addresses `0x48` to `0x4F`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Address(u8);
+
+impl Address {
+    /// Builds the address from the level of the A0, A1 and A2 pins
+    pub const fn from_pins(a0: bool, a1: bool, a2: bool) -> Address {
+        Address(0x48 | (a2 as u8) << 2 | (a1 as u8) << 1 | a0 as u8)
+    }
+
+    /// Builds the address from the device number, i.e. `A2 A1 A0` pins as a
+    /// 3 bits number, `None` if it is not in `0..8`
+    pub fn from_device(device: u8) -> Option<Address> {
+        if device < 8 {
+            Some(Address(0x48 | device))
+        } else {
+            None
+        }
+    }
+
+    /// Validates a raw 7-bit address, `None` if it is not in `0x48..=0x4F`
+    pub fn new(address: u16) -> Option<Address> {
+        match address {
+            0x48..=0x4F => Some(Address(address as u8)),
+            _ => None,
+        }
+    }
+
+    /// Gets the 7-bit address
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    /// Gets the device number, i.e. `A2 A1 A0` pins as a 3 bits number
+    pub fn device(&self) -> u8 {
+        self.0 & 0x07
+    }
+}
+
+impl Default for Address {
+    /// All address pins low (`0x48`)
+    fn default() -> Address {
+        Address(0x48)
+    }
 }
 
 /// An input Pin enumeration corresponding to the physical analog inputs pins
@@ -54,69 +163,638 @@ pub enum Pin {
     AIN3,
 }
 
-impl PCF8591 {
+/// A differential input enumeration corresponding to the pair of analog input pins
+/// being compared (positive input first)
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffPin {
+    /// AIN0 - AIN3
+    AIN0_AIN3,
+    /// AIN1 - AIN3
+    AIN1_AIN3,
+    /// AIN2 - AIN3
+    AIN2_AIN3,
+    /// AIN0 - AIN1
+    AIN0_AIN1,
+}
+
+/// Analog input programming, as per Fig 5.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InputMode {
+    /// Four single-ended inputs
+    SingleEnded,
+    /// Three differential inputs, all relative to AIN3
+    ThreeDifferential,
+    /// AIN0 and AIN1 single-ended, AIN2 - AIN3 differential
+    Mixed,
+    /// Two differential inputs, AIN0 - AIN1 and AIN2 - AIN3
+    TwoDifferential,
+}
+
+impl Default for InputMode {
+    /// Four single-ended inputs, the power-on mode
+    fn default() -> InputMode {
+        InputMode::SingleEnded
+    }
+}
+
+impl InputMode {
+    /// Inputs provided by this mode, in channel order
+    pub fn channels(&self) -> &'static [Channel] {
+        match *self {
+            InputMode::SingleEnded => &[
+                Channel::Single(Pin::AIN0),
+                Channel::Single(Pin::AIN1),
+                Channel::Single(Pin::AIN2),
+                Channel::Single(Pin::AIN3),
+            ],
+            InputMode::ThreeDifferential => &[
+                Channel::Differential(DiffPin::AIN0_AIN3),
+                Channel::Differential(DiffPin::AIN1_AIN3),
+                Channel::Differential(DiffPin::AIN2_AIN3),
+            ],
+            InputMode::Mixed => &[
+                Channel::Single(Pin::AIN0),
+                Channel::Single(Pin::AIN1),
+                Channel::Differential(DiffPin::AIN2_AIN3),
+            ],
+            InputMode::TwoDifferential => &[
+                Channel::Differential(DiffPin::AIN0_AIN1),
+                Channel::Differential(DiffPin::AIN2_AIN3),
+            ],
+        }
+    }
+
+    /// Bits 4 and 5 of the control byte
+    fn bits(&self) -> u8 {
+        match *self {
+            InputMode::SingleEnded => 0x00,
+            InputMode::ThreeDifferential => 0x10,
+            InputMode::Mixed => 0x20,
+            InputMode::TwoDifferential => 0x30,
+        }
+    }
+}
+
+impl Pin {
+    /// Input mode and channel number used to read this pin
+    pub fn channel(&self) -> (InputMode, u8) {
+        match *self {
+            Pin::AIN0 => (InputMode::SingleEnded, 0),
+            Pin::AIN1 => (InputMode::SingleEnded, 1),
+            Pin::AIN2 => (InputMode::SingleEnded, 2),
+            Pin::AIN3 => (InputMode::SingleEnded, 3),
+        }
+    }
+}
+
+impl DiffPin {
+    /// Input mode and channel number used to read this differential input
+    pub fn channel(&self) -> (InputMode, u8) {
+        match *self {
+            DiffPin::AIN0_AIN3 => (InputMode::ThreeDifferential, 0),
+            DiffPin::AIN1_AIN3 => (InputMode::ThreeDifferential, 1),
+            DiffPin::AIN2_AIN3 => (InputMode::ThreeDifferential, 2),
+            DiffPin::AIN0_AIN1 => (InputMode::TwoDifferential, 0),
+        }
+    }
+}
+
+/// Any input which can be read by the converter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    /// A single-ended input pin
+    Single(Pin),
+    /// A differential input
+    Differential(DiffPin),
+}
+
+impl Channel {
+    /// Input mode and channel number used to read this input
+    pub fn channel(&self) -> (InputMode, u8) {
+        match *self {
+            Channel::Single(pin) => pin.channel(),
+            Channel::Differential(pin) => pin.channel(),
+        }
+    }
+}
+
+impl From<Pin> for Channel {
+    fn from(pin: Pin) -> Channel {
+        Channel::Single(pin)
+    }
+}
+
+impl From<DiffPin> for Channel {
+    fn from(pin: DiffPin) -> Channel {
+        Channel::Differential(pin)
+    }
+}
+
+#[cfg(feature = "std")]
+impl PCF8591<LinuxBus> {
 
     /// Creates a new connection given i2c path and address
     ///
     /// - `path`: device slave path (e.g. `/dev/i2c-1`)
-    /// - `address`: has to be defined as per Table 5 (`0x48` per default)
+    /// - `address`: as wired on A0, A1 and A2 pins (`0x48` per default)
     /// - `v_ref`: is the board voltage (e.g. typically 3.3V on raspberry pi)
-    pub fn new<P: AsRef<Path>>(path: P, address: u16, v_ref: f64) -> Result<PCF8591> {
-        LinuxI2CDevice::new(path, address)
-            .map(|i2c| PCF8591 { 
-                i2c: i2c, 
-                pin: None, 
-                v_lsb: v_ref / 255.,
-            })
+    pub fn new<P: AsRef<Path>>(path: P, address: Address, v_ref: f64) -> Result<PCF8591<LinuxBus>> {
+        let i2c = LinuxBus::new(path).map_err(|e| Error::I2c(LinuxBusError(e)))?;
+        Ok(PCF8591::from_i2c(i2c, address, v_ref))
+    }
+
+    /// Creates a new PCF8591 on the i2c bus at given path, configured by a `Profile`
+    pub fn open<P: AsRef<Path>>(path: P, profile: &Profile) -> Result<PCF8591<LinuxBus>> {
+        let i2c = LinuxBus::new(path).map_err(|e| Error::I2c(LinuxBusError(e)))?;
+        Ok(PCF8591::from_profile(i2c, profile))
+    }
+}
+
+/// Probes the addresses `0x48` to `0x4F` of the i2c bus at given path
+///
+/// Returns the addresses where a device answered, which can be passed to
+/// `PCF8591::new`. Probing only reads, leaving the configuration of the converters
+/// and their analog output untouched, see `bus::detect`.
+///
+/// ```rust,should_panic
+/// let addresses = pcf8591::probe("/dev/i2c-1").unwrap();
+/// println!("PCF8591 found at {:?}", addresses);
+/// ```
+#[cfg(feature = "std")]
+pub fn probe<P: AsRef<Path>>(path: P) -> Result<Vec<Address>> {
+    let mut i2c = LinuxBus::new(path).map_err(|e| Error::I2c(LinuxBusError(e)))?;
+    Ok((0..8)
+        .filter_map(Address::from_device)
+        .filter(|&address| bus::detect(&mut i2c, address))
+        .collect())
+}
+
+impl<I2C: I2c> PCF8591<I2C> {
+
+    /// Creates a new converter over an existing I2C bus
+    ///
+    /// - `i2c`: any blocking embedded-hal I2C bus
+    /// - `address`: as wired on A0, A1 and A2 pins (`0x48` per default)
+    /// - `v_ref`: is the board voltage (e.g. typically 3.3V on raspberry pi)
+    pub fn from_i2c(i2c: I2C, address: Address, v_ref: f64) -> PCF8591<I2C> {
+        PCF8591 {
+            i2c,
+            state: State::new(address, v_ref),
+        }
+    }
+
+    /// Creates a new PCF8591 over any embedded-hal I2C bus, configured by a `Profile`
+    pub fn from_profile(i2c: I2C, profile: &Profile) -> PCF8591<I2C> {
+        PCF8591 {
+            i2c,
+            state: State::from_profile(profile),
+        }
+    }
+
+    /// Gets the current configuration, e.g. to save it after a calibration
+    pub fn profile(&self) -> Profile {
+        self.state.profile()
+    }
+
+    /// Sets the analog ground voltage (0V per default)
+    ///
+    /// Voltages are then converted as per Fig 9., with
+    /// V_LSB = (V_REF - V_AGND) / 256
+    pub fn set_v_agnd(&mut self, v_agnd: f64) {
+        self.state.v_agnd = v_agnd;
+    }
+
+    /// Gets the reference voltage
+    pub fn v_ref(&self) -> f64 {
+        self.state.v_ref
+    }
+
+    /// Gets the analog ground voltage
+    pub fn v_agnd(&self) -> f64 {
+        self.state.v_agnd
+    }
+
+    /// Sets the input mode the board is wired for, see `InputMode::channels`
+    ///
+    /// It is only stored in the profile, each read selecting the mode of the
+    /// channel it reads.
+    pub fn set_input_mode(&mut self, mode: InputMode) {
+        self.state.input_mode = mode;
+    }
+
+    /// Gets the input mode the board is wired for
+    pub fn input_mode(&self) -> InputMode {
+        self.state.input_mode
+    }
+
+    /// Sets the calibration of an input pin, applied to its single-ended readings
+    ///
+    /// Differential readings are not calibrated.
+    pub fn set_calibration(&mut self, pin: Pin, calibration: Calibration) {
+        self.state.calibrations[pin as usize] = calibration;
+    }
+
+    /// Gets the calibration of an input pin
+    pub fn calibration(&self, pin: Pin) -> Calibration {
+        self.state.calibrations[pin as usize]
+    }
+
+    /// Sets the calibration of the analog output, applied to written voltages
+    pub fn set_output_calibration(&mut self, calibration: Calibration) {
+        self.state.output_calibration = calibration;
+    }
+
+    /// Gets the calibration of the analog output
+    pub fn output_calibration(&self) -> Calibration {
+        self.state.output_calibration
+    }
+
+    /// Converts a code into the nominal, non calibrated, pin voltage
+    ///
+    /// Returns v_agnd + code * (v_ref - v_agnd) / 256, as per Fig 8. and 9.,
+    /// e.g. to feed `Calibration::two_point`.
+    pub fn code_to_voltage(&self, code: u8) -> f64 {
+        self.state.code_to_voltage(code)
+    }
+
+    /// Destroys the converter and gives back the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Reads a single byte from the converter
+    fn read_byte(&mut self) -> Result<u8, I2C::Error> {
+        let mut buf = [0];
+        self.state.start_read();
+        self.i2c.read(self.state.address.value(), &mut buf)?;
+        Ok(buf[0])
+    }
+
+    /// Sends the control byte if needed then reads the last conversion
+    fn read_control(&mut self, control_byte: u8) -> Result<u8, I2C::Error> {
+        if self.state.control != Some(control_byte) {
+            self.i2c.write(self.state.address.value(), &[control_byte])?;
+            self.read_byte()?; // previous byte, unspecified
+            self.state.control = Some(control_byte);
+        }
+        self.read_byte()
+    }
+
+    /// Reads successive conversions of one input pin in a single read transaction
+    ///
+    /// Fills `buffer` with digital bytes, as `analog_read_byte`. Auto-increment
+    /// is disabled so every acknowledged byte starts a new conversion of the
+    /// same pin, conversions being spaced by the duration of one byte on the
+    /// bus. The leading byte, holding a conversion preceding the transaction,
+    /// is read in the same transaction and discarded.
+    pub fn read_burst(&mut self, pin: Pin, buffer: &mut [u8]) -> Result<(), I2C::Error> {
+        let control_byte = self.state.read_control(pin.channel());
+        if self.state.control != Some(control_byte) {
+            self.i2c.write(self.state.address.value(), &[control_byte])?;
+            self.state.control = Some(control_byte);
+        }
+        let mut stale = [0];
+        self.state.start_read();
+        self.i2c.transaction(
+            self.state.address.value(),
+            &mut [Operation::Read(&mut stale), Operation::Read(buffer)],
+        )?;
+        Ok(())
+    }
+
+    /// Reads `samples` conversions of one input pin and sums them
+    fn read_sum(&mut self, pin: Pin, samples: u32) -> Result<u32, I2C::Error> {
+        let mut buffer = [0; BURST_CHUNK];
+        let mut remaining = samples as usize;
+        let mut sum = 0;
+        while remaining > 0 {
+            let chunk = &mut buffer[..remaining.min(BURST_CHUNK)];
+            self.read_burst(pin, chunk)?;
+            sum += chunk.iter().map(|&b| b as u32).sum::<u32>();
+            remaining -= chunk.len();
+        }
+        Ok(sum)
+    }
+
+    /// Sets the oversampling of an input pin
+    ///
+    /// Applied by every reading of the pin alone: `analog_read`,
+    /// `analog_read_channel`, `read_sensor`, `sample` and thus `sampler`. Byte
+    /// reads and scans of all pins stay single conversions.
+    pub fn set_oversampling(&mut self, pin: Pin, oversampling: Oversampling) {
+        self.state.oversampling[pin as usize] = oversampling;
+    }
+
+    /// Gets the oversampling of an input pin
+    pub fn oversampling(&self, pin: Pin) -> Oversampling {
+        self.state.oversampling[pin as usize]
+    }
+
+    /// Reads several conversions of an input pin and combines them into a code
+    ///
+    /// Returns the code, with a fractional part, in `0. ..256.`. See `Oversampling`.
+    pub fn read_oversampled(&mut self, pin: Pin, oversampling: Oversampling) -> Result<f64, I2C::Error> {
+        let sum = self.read_sum(pin, oversampling.samples())?;
+        Ok(oversampling.code(sum))
+    }
+
+    /// Reads `4^bits` conversions of an input pin and decimates them
+    ///
+    /// Returns a fixed-point code of `8 + bits` bits, `bits` being at most 8.
+    pub fn read_decimated(&mut self, pin: Pin, bits: u8) -> Result<u16, I2C::Error> {
+        let bits = bits.min(Oversampling::MAX_BITS);
+        let sum = self.read_sum(pin, Oversampling::Decimate(bits).samples())?;
+        Ok((sum >> bits) as u16)
     }
 
     /// Reads analog values out of input pin and output digital byte
     ///
     /// The conversion with board voltage is left to the user.
     /// For automatic conversion, use `analog_read`
-    pub fn analog_read_byte(&mut self, pin: Pin) -> Result<u8> {
-        match self.pin {
-            Some(ref p) if *p == pin => (), 
-            _ => {
-                // need to change control_byte, as per Fig 4.
-                let control_byte = match pin {
-                    Pin::AIN0 => 0x40,
-                    Pin::AIN1 => 0x41,
-                    Pin::AIN2 => 0x42,
-                    Pin::AIN3 => 0x43,
-                };
-                let _ = try!(self.i2c.smbus_write_byte(control_byte));
-                let _ = try!(self.i2c.smbus_read_byte()); // previous byte, unspecified
-                self.pin = Some(pin);
-            }
-        }
-        self.i2c.smbus_read_byte()
+    pub fn analog_read_byte(&mut self, pin: Pin) -> Result<u8, I2C::Error> {
+        let control_byte = self.state.read_control(pin.channel());
+        self.read_control(control_byte)
+    }
+
+    /// Reads the difference between two analog inputs and output a signed digital byte
+    ///
+    /// The converter switches to the corresponding differential input mode.
+    /// For automatic conversion, use `analog_read_differential`
+    pub fn analog_read_differential_byte(&mut self, pin: DiffPin) -> Result<i8, I2C::Error> {
+        let control_byte = self.state.read_control(pin.channel());
+        // differential results are in two's complement, as per Fig. 10
+        self.read_control(control_byte).map(|b| b as i8)
     }
     
     /// Reads analog values out of input pin and output corresponding input voltage
     ///
-    /// Returns analog_read_byte * v_ref / 255 (suppose Vagnd == 0)
-    pub fn analog_read(&mut self, pin: Pin) -> Result<f64> {
-        // converts read byte as per Fig. 9
-        self.analog_read_byte(pin)
-            .map(|b| b as f64  * self.v_lsb)
+    /// Returns v_agnd + analog_read_byte * (v_ref - v_agnd) / 256, corrected
+    /// by the pin calibration (see `set_calibration`). The code is oversampled
+    /// if configured with `set_oversampling`.
+    pub fn analog_read(&mut self, pin: Pin) -> Result<f64, I2C::Error> {
+        self.read_pin(pin).map(|(_, v)| v)
+    }
+
+    /// Reads an input pin, oversampled if configured, as its code and calibrated voltage
+    ///
+    /// The code of an oversampled pin is the nearest code of the mean.
+    fn read_pin(&mut self, pin: Pin) -> Result<(u8, f64), I2C::Error> {
+        match self.state.oversampling[pin as usize] {
+            Oversampling::None => self.analog_read_byte(pin)
+                .map(|b| (b, self.state.pin_voltage(pin, b))),
+            oversampling => self.read_oversampled(pin, oversampling)
+                .map(|code| self.state.oversampled_reading(pin, code)),
+        }
+    }
+
+    /// Reads the difference between two analog inputs and output corresponding voltage
+    ///
+    /// Returns analog_read_differential_byte * (v_ref - v_agnd) / 256
+    pub fn analog_read_differential(&mut self, pin: DiffPin) -> Result<f64, I2C::Error> {
+        self.analog_read_differential_byte(pin)
+            .map(|b| self.state.diff_to_voltage(b))
+    }
+
+    /// Reads either a single-ended or a differential input and output corresponding voltage
+    ///
+    /// See `analog_read` and `analog_read_differential`.
+    pub fn analog_read_channel(&mut self, channel: Channel) -> Result<f64, I2C::Error> {
+        match channel {
+            Channel::Single(pin) => self.analog_read(pin),
+            Channel::Differential(pin) => self.analog_read_differential(pin),
+        }
+    }
+
+    /// Reads an input pin and converts it into a physical quantity
+    ///
+    /// The conversion gets both the code and the calibrated voltage, see
+    /// `sensor`. Both are oversampled if configured with `set_oversampling`,
+    /// the code being rounded.
+    pub fn read_sensor<C: Conversion>(&mut self, pin: Pin, conversion: &C) -> Result<f64, I2C::Error> {
+        let (code, voltage) = self.read_pin(pin)?;
+        Ok(conversion.convert(code, voltage))
+    }
+
+    /// Reads either a single-ended or a differential input as a timestamped `Sample`
+    ///
+    /// The timestamp is the start of the conversion, i.e. the start of the
+    /// read transaction preceding the one returning the value. Oversampled
+    /// pins are read as per `read_sensor`, timestamped by the start of their
+    /// last burst read.
+    #[cfg(feature = "std")]
+    pub fn sample(&mut self, channel: Channel) -> Result<Sample, I2C::Error> {
+        if let Channel::Single(pin) = channel {
+            if self.state.oversampling[pin as usize] != Oversampling::None {
+                let (code, voltage) = self.read_pin(pin)?;
+                let timestamp = self.state.converted_at.unwrap_or_else(Instant::now);
+                return Ok(Sample::new(channel, code, voltage, timestamp));
+            }
+        }
+        let control_byte = self.state.read_control(channel.channel());
+        let code = self.read_control(control_byte)?;
+        let timestamp = self.state.acquired_at.unwrap_or_else(Instant::now);
+        Ok(Sample::new(channel, code, self.state.channel_voltage(channel, code), timestamp))
+    }
+
+    /// Reads all four input pins, in order, as timestamped `Sample`s
+    ///
+    /// All conversions happen in the same read transaction (see `read_all`)
+    /// and share its start as timestamp.
+    #[cfg(feature = "std")]
+    pub fn sample_all(&mut self) -> Result<[Sample; 4], I2C::Error> {
+        let codes = self.read_all()?;
+        let timestamp = self.state.converted_at.unwrap_or_else(Instant::now);
+        let pins = [Pin::AIN0, Pin::AIN1, Pin::AIN2, Pin::AIN3];
+        Ok(pins.map(|pin| {
+            let code = codes[pin as usize];
+            Sample::new(Channel::Single(pin), code, self.state.pin_voltage(pin, code), timestamp)
+        }))
+    }
+
+    /// Creates an iterator reading `channels` at a fixed `rate`, in Hz
+    ///
+    /// See `sampler::Sampler`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not strictly positive and finite
+    #[cfg(feature = "std")]
+    pub fn sampler(&mut self, channels: &[Channel], rate: f64) -> Sampler<'_, I2C> {
+        Sampler::new(self, channels, rate)
+    }
+
+    /// Reads all four input pins, in order, as digital bytes
+    ///
+    /// Enables the auto-increment flag so that a single read transaction returns
+    /// AIN0 to AIN3 in sequence. The first byte of the transaction holds the
+    /// previous conversion and is discarded.
+    pub fn read_all(&mut self) -> Result<[u8; 4], I2C::Error> {
+        let control_byte = self.state.scan_control();
+        // always resend the control byte so that the channel counter restarts at AIN0
+        self.i2c.write(self.state.address.value(), &[control_byte])?;
+        self.state.control = Some(control_byte);
+        let mut buf = [0; 5];
+        self.state.start_read();
+        self.i2c.read(self.state.address.value(), &mut buf)?;
+        Ok([buf[1], buf[2], buf[3], buf[4]])
+    }
+
+    /// Reads all four input pins, in order, and output corresponding input voltages
+    ///
+    /// Returns v_agnd + read_all * (v_ref - v_agnd) / 256, corrected by the
+    /// pin calibrations
+    pub fn analog_read_all(&mut self) -> Result<[f64; 4], I2C::Error> {
+        let bytes = self.read_all()?;
+        Ok(self.state.scan_voltages(bytes))
+    }
+
+    /// Enables the analog output
+    ///
+    /// The output is enabled per default. The last written value is held on
+    /// AOUT, including while reading inputs.
+    pub fn enable_output(&mut self) -> Result<(), I2C::Error> {
+        self.set_output(true)
+    }
+
+    /// Disables the analog output
+    ///
+    /// AOUT is switched to high-impedance and the internal oscillator is
+    /// powered down when no conversion is running. Reads and writes keep the
+    /// output disabled until `enable_output` is called, writes only updating
+    /// the DAC data register.
+    pub fn disable_output(&mut self) -> Result<(), I2C::Error> {
+        self.set_output(false)
+    }
+
+    /// Returns true if the analog output is enabled
+    pub fn is_output_enabled(&self) -> bool {
+        self.state.output_enabled
+    }
+
+    /// Sends a control byte with the new output state
+    fn set_output(&mut self, enabled: bool) -> Result<(), I2C::Error> {
+        let control_byte = self.state.output_control(enabled);
+        self.state.control = None;
+        self.i2c.write(self.state.address.value(), &[control_byte])?;
+        self.state.output_enabled = enabled;
+        Ok(())
     }
 
     /// Writes analog values, as byte, in the output pin
     ///
     /// The conversion with board voltage is left to the user
     /// For automatic conversion, use `analog_write`
-    pub fn analog_write_byte(&mut self, value: u8) -> Result<()> {
-        self.pin = None;
+    pub fn analog_write_byte(&mut self, value: u8) -> Result<(), I2C::Error> {
+        self.state.control = None;
         // if we send 3 bytes, then it is a D/A conversion
-        self.i2c.write(&[0x40, value])
+        let control_byte = self.state.write_control();
+        self.i2c.write(self.state.address.value(), &[control_byte, value])?;
+        self.state.dac = value;
+        Ok(())
+    }
+
+    /// Writes successive DAC values in a single write transaction
+    ///
+    /// The values follow one control byte, each one updating the output in
+    /// turn as soon as it is received, so the update rate is only limited by
+    /// the bus speed. Nothing is sent if `values` is empty.
+    pub fn write_samples(&mut self, values: &[u8]) -> Result<(), I2C::Error> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        self.state.control = None;
+        let control_byte = self.state.write_control();
+        // adjacent writes are sent as a single write, without restart
+        self.i2c.transaction(
+            self.state.address.value(),
+            &mut [Operation::Write(&[control_byte]), Operation::Write(values)],
+        )?;
+        self.state.dac = values[values.len() - 1];
+        Ok(())
+    }
+
+    /// Writes successive DAC values, `chunk` values per write transaction
+    ///
+    /// For buses limiting the length of a transfer, see `write_samples`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk` is 0
+    pub fn write_samples_chunked(&mut self, values: &[u8], chunk: usize) -> Result<(), I2C::Error> {
+        values.chunks(chunk).try_for_each(|c| self.write_samples(c))
+    }
+
+    /// Writes successive voltages in the output pin, as streamed DAC values
+    ///
+    /// Voltages are converted as per `analog_write` and sent in write
+    /// transactions of up to 64 values. Returns `Error::VoltageOutOfRange`
+    /// without writing anything if any voltage cannot be output.
+    pub fn analog_write_samples(&mut self, voltages: &[f64]) -> Result<(), I2C::Error> {
+        self.state.check_voltages(voltages)?;
+        let mut codes = [0; WRITE_CHUNK];
+        for chunk in voltages.chunks(WRITE_CHUNK) {
+            for (code, &v) in codes.iter_mut().zip(chunk) {
+                *code = self.state.voltage_to_code(v)?;
+            }
+            self.write_samples(&codes[..chunk.len()])?;
+        }
+        Ok(())
     }
 
     /// Writes analog values in the output pin
-    pub fn analog_write(&mut self, v_out: f64) -> Result<()> {
-        let value = (v_out / self.v_lsb) as u8;
-        self.analog_write_byte(value)
+    ///
+    /// The voltage, corrected by the output calibration (see
+    /// `set_output_calibration`), is rounded to the nearest DAC code, as per
+    /// Fig 8., `v_ref` itself being written as the full scale code. Returns
+    /// `Error::VoltageOutOfRange` without writing anything if `v_out` is
+    /// NaN or its pin voltage is outside of `v_agnd..=v_ref`.
+    pub fn analog_write(&mut self, v_out: f64) -> Result<(), I2C::Error> {
+        let code = self.state.voltage_to_code(v_out)?;
+        self.analog_write_byte(code)
     }
 
-}
+    /// Writes analog values in the output pin, saturating out of range voltages
+    ///
+    /// The calibrated voltage is rounded to the nearest DAC code, voltages below `v_agnd`
+    /// are written as the zero code and voltages above `v_ref` as the full
+    /// scale code. Returns `Error::VoltageOutOfRange` without writing anything
+    /// if `v_out` is NaN.
+    pub fn analog_write_saturating(&mut self, v_out: f64) -> Result<(), I2C::Error> {
+        let code = self.state.voltage_to_code_saturating(v_out)?;
+        self.analog_write_byte(code)
+    }
 
+    /// Sweeps the DAC and reads it back on an input pin wired to AOUT
+    ///
+    /// Every DAC code is written with `analog_write_byte` then read back
+    /// `test.samples` times in a burst on `test.pin`, see `selftest`. The
+    /// output is enabled during the sweep and disabled again afterwards if it
+    /// was, the DAC getting back its last written code. Returns
+    /// `Error::SelfTestFailed` if the report exceeds `test.tolerances`.
+    pub fn self_test(&mut self, test: &SelfTest) -> Result<Report, I2C::Error> {
+        let (enabled, dac) = (self.state.output_enabled, self.state.dac);
+        if !enabled {
+            self.enable_output()?;
+        }
+        let mut sweep = Sweep::new();
+        let mut buffer = [0; 255];
+        let readings = &mut buffer[..test.samples.max(1) as usize];
+        for code in 0..=255 {
+            self.analog_write_byte(code)?;
+            self.read_burst(test.pin, readings)?;
+            sweep.record(code, readings);
+        }
+        self.analog_write_byte(dac)?;
+        if !enabled {
+            self.disable_output()?;
+        }
+        let report = sweep.report();
+        if report.passes(&test.tolerances) {
+            Ok(report)
+        } else {
+            Err(Error::SelfTestFailed(report))
+        }
+    }
+}
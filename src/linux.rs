@@ -0,0 +1,102 @@
+//! Linux backend, exposing an i2c character device as an embedded-hal I2C bus
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use embedded_hal::i2c::{self, I2c, Operation};
+use i2cdev::core::{I2CMessage, I2CTransfer};
+use i2cdev::linux::{LinuxI2CBus, LinuxI2CError, LinuxI2CMessage};
+
+/// An I2C bus opened from a linux device path (e.g. `/dev/i2c-1`)
+pub struct LinuxBus {
+    bus: LinuxI2CBus,
+}
+
+impl LinuxBus {
+    /// Opens the i2c bus at given path
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<LinuxBus, LinuxI2CError> {
+        LinuxI2CBus::new(path).map(|bus| LinuxBus { bus })
+    }
+}
+
+/// Wrapper over LinuxI2CError, returned by `LinuxBus` transactions
+#[derive(Debug)]
+pub struct LinuxBusError(pub LinuxI2CError);
+
+impl fmt::Display for LinuxBusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl Error for LinuxBusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl i2c::Error for LinuxBusError {
+    fn kind(&self) -> i2c::ErrorKind {
+        i2c::ErrorKind::Other
+    }
+}
+
+impl i2c::ErrorType for LinuxBus {
+    type Error = LinuxBusError;
+}
+
+impl I2c for LinuxBus {
+    /// Adjacent operations of the same type are merged in one message, as
+    /// required by embedded-hal, the kernel restarting between messages
+    fn transaction(&mut self, address: u8, operations: &mut [Operation]) -> Result<(), LinuxBusError> {
+        // (is read, data) of each group of adjacent operations
+        let mut groups: Vec<(bool, Vec<u8>)> = Vec::new();
+        for op in operations.iter() {
+            let (read, bytes): (bool, &[u8]) = match *op {
+                Operation::Read(ref buf) => (true, buf),
+                Operation::Write(buf) => (false, buf),
+            };
+            match groups.last_mut() {
+                Some(&mut (r, ref mut data)) if r == read => data.extend_from_slice(bytes),
+                _ => groups.push((read, bytes.to_vec())),
+            }
+        }
+        if groups.is_empty() {
+            return Ok(());
+        }
+
+        let mut messages = groups
+            .iter_mut()
+            .map(|&mut (read, ref mut data)| {
+                let message = if read {
+                    LinuxI2CMessage::read(data)
+                } else {
+                    LinuxI2CMessage::write(data)
+                };
+                message.with_address(address as u16)
+            })
+            .collect::<Vec<_>>();
+        self.bus.transfer(&mut messages).map_err(LinuxBusError)?;
+        drop(messages);
+
+        // splits the data read back into the buffers of the operations
+        let mut group = 0;
+        let mut offset = 0;
+        let mut previous = None;
+        for op in operations.iter_mut() {
+            let read = matches!(*op, Operation::Read(_));
+            if previous.is_some() && previous != Some(read) {
+                group += 1;
+                offset = 0;
+            }
+            previous = Some(read);
+            if let Operation::Read(ref mut buf) = *op {
+                let len = buf.len();
+                buf.copy_from_slice(&groups[group].1[offset..offset + len]);
+                offset += len;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+//! A simulated PCF8591, to run code without the physical chip
+//!
+//! `MockPCF8591` implements the embedded-hal I2C traits and emulates the
+//! control register, the one conversion lag of reads, auto-increment, the
+//! four input modes and the DAC register. Adjacent operations of the same type
+//! form a single write or read, as on a real bus. Clones share the same device so the
+//! input voltages can be changed while the converter owns the bus.
+//!
+//! ```rust
+//! use pcf8591::{Address, PCF8591, Pin};
+//! use pcf8591::mock::MockPCF8591;
+//!
+//! let mock = MockPCF8591::new(0x48, 3.3);
+//! mock.set_input(Pin::AIN0, 1.65);
+//!
+//! let mut converter = PCF8591::from_i2c(mock.clone(), Address::default(), 3.3);
+//! assert_eq!(converter.analog_read_byte(Pin::AIN0).unwrap(), 128);
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+
+use crate::Pin;
+
+/// A transaction received by the simulated device
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transaction {
+    /// Bytes written by the master
+    Write(Vec<u8>),
+    /// Bytes returned to the master
+    Read(Vec<u8>),
+}
+
+/// State of the simulated chip
+struct State {
+    address: u8,
+    v_ref: f64,
+    v_agnd: f64,
+    inputs: [f64; 4],
+    loopback: Option<usize>,
+    control: u8,
+    channel: u8,
+    data: u8,
+    dac: u8,
+    transactions: Vec<Transaction>,
+}
+
+/// A simulated PCF8591 device
+///
+/// All clones share the same simulated chip.
+#[derive(Clone)]
+pub struct MockPCF8591 {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockPCF8591 {
+    /// Creates a new simulated device, with all inputs at 0V
+    ///
+    /// - `address`: 7-bit address the device acknowledges
+    /// - `v_ref`: is the board voltage
+    pub fn new(address: u8, v_ref: f64) -> MockPCF8591 {
+        MockPCF8591 {
+            state: Arc::new(Mutex::new(State {
+                address,
+                v_ref,
+                v_agnd: 0.,
+                inputs: [0.; 4],
+                loopback: None,
+                control: 0,
+                channel: 0,
+                // data register value after power-on reset
+                data: 0x80,
+                dac: 0,
+                transactions: Vec::new(),
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sets the analog ground voltage (0V per default)
+    pub fn set_v_agnd(&self, v_agnd: f64) {
+        self.state().v_agnd = v_agnd;
+    }
+
+    /// Sets the voltage applied on an input pin
+    pub fn set_input(&self, pin: Pin, voltage: f64) {
+        self.state().inputs[pin as usize] = voltage;
+    }
+
+    /// Wires AOUT to an input pin, the pin following the output while it is enabled
+    pub fn connect_output(&self, pin: Pin) {
+        self.state().loopback = Some(pin as usize);
+    }
+
+    /// Gets the content of the control register
+    pub fn control(&self) -> u8 {
+        self.state().control
+    }
+
+    /// Gets the content of the DAC data register
+    pub fn dac(&self) -> u8 {
+        self.state().dac
+    }
+
+    /// Gets the voltage on AOUT, `None` if the output is disabled (high-impedance)
+    pub fn output_voltage(&self) -> Option<f64> {
+        self.state().output()
+    }
+
+    /// Gets all transactions received since creation or last `clear_transactions`
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.state().transactions.clone()
+    }
+
+    /// Clears the transaction log
+    pub fn clear_transactions(&self) {
+        self.state().transactions.clear();
+    }
+}
+
+impl State {
+    fn lsb(&self) -> f64 {
+        (self.v_ref - self.v_agnd) / 256.
+    }
+
+    fn output(&self) -> Option<f64> {
+        if self.control & 0x40 == 0 {
+            None
+        } else {
+            Some(self.v_agnd + self.dac as f64 * self.lsb())
+        }
+    }
+
+    /// Voltage on an input pin, driven by AOUT if wired to it
+    fn input(&self, input: usize) -> f64 {
+        match self.output() {
+            Some(v) if self.loopback == Some(input) => v,
+            _ => self.inputs[input],
+        }
+    }
+
+    /// Number of channels available in the current input mode, as per Fig 5.
+    fn channels(&self) -> u8 {
+        match self.control & 0x30 {
+            0x00 => 4,
+            0x10 | 0x20 => 3,
+            _ => 2,
+        }
+    }
+
+    fn single(&self, input: usize) -> u8 {
+        let code = ((self.input(input) - self.v_agnd) / self.lsb()).round();
+        code.clamp(0., 255.) as u8
+    }
+
+    fn differential(&self, positive: usize, negative: usize) -> u8 {
+        let code = ((self.input(positive) - self.input(negative)) / self.lsb()).round();
+        code.clamp(-128., 127.) as i8 as u8
+    }
+
+    /// Converts the current channel
+    fn convert(&self) -> u8 {
+        match (self.control & 0x30, self.channel) {
+            (0x00, c) => self.single(c as usize),
+            (0x10, c) => self.differential(c as usize, 3),
+            (0x20, 2) => self.differential(2, 3),
+            (0x20, c) => self.single(c as usize),
+            (_, 0) => self.differential(0, 1),
+            (_, _) => self.differential(2, 3),
+        }
+    }
+
+    fn write(&mut self, bytes: Vec<u8>) {
+        if let Some((&control, values)) = bytes.split_first() {
+            self.control = control & 0x77;
+            self.channel = (control & 0x03) % self.channels();
+            if let Some(&value) = values.last() {
+                self.dac = value;
+            }
+        }
+        self.transactions.push(Transaction::Write(bytes));
+    }
+
+    fn read(&mut self, buf: &mut [u8]) {
+        // each byte sends the previous conversion while converting the current channel
+        for b in buf.iter_mut() {
+            *b = self.data;
+            self.data = self.convert();
+            if self.control & 0x04 != 0 {
+                self.channel = (self.channel + 1) % self.channels();
+            }
+        }
+    }
+
+    /// Logs the current write or read, if any
+    fn flush(&mut self, pending: &mut Option<Transaction>) {
+        match pending.take() {
+            Some(Transaction::Write(bytes)) => self.write(bytes),
+            Some(read) => self.transactions.push(read),
+            None => (),
+        }
+    }
+}
+
+impl ErrorType for MockPCF8591 {
+    type Error = ErrorKind;
+}
+
+impl I2c for MockPCF8591 {
+    fn transaction(&mut self, address: u8, operations: &mut [Operation]) -> Result<(), ErrorKind> {
+        let mut state = self.state();
+        if address != state.address {
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+        let mut pending = None;
+        for op in operations {
+            match (op, &mut pending) {
+                (Operation::Write(bytes), Some(Transaction::Write(written))) => written.extend_from_slice(bytes),
+                (Operation::Write(bytes), _) => {
+                    state.flush(&mut pending);
+                    pending = Some(Transaction::Write(bytes.to_vec()));
+                }
+                (Operation::Read(buf), Some(Transaction::Read(read))) => {
+                    state.read(buf);
+                    read.extend_from_slice(buf);
+                }
+                (Operation::Read(buf), _) => {
+                    state.flush(&mut pending);
+                    state.read(buf);
+                    pending = Some(Transaction::Read(buf.to_vec()));
+                }
+            }
+        }
+        state.flush(&mut pending);
+        Ok(())
+    }
+}
+
+/// A simulated I2C bus with several PCF8591 devices attached
+///
+/// Transactions are dispatched to the device acknowledging the address.
+#[derive(Clone, Default)]
+pub struct MockBus {
+    devices: Vec<MockPCF8591>,
+}
+
+impl MockBus {
+    /// Creates a new bus without any device
+    pub fn new() -> MockBus {
+        MockBus::default()
+    }
+
+    /// Attaches a simulated device to the bus
+    pub fn attach(&mut self, device: MockPCF8591) {
+        self.devices.push(device);
+    }
+}
+
+impl ErrorType for MockBus {
+    type Error = ErrorKind;
+}
+
+impl I2c for MockBus {
+    fn transaction(&mut self, address: u8, operations: &mut [Operation]) -> Result<(), ErrorKind> {
+        match self.devices.iter_mut().find(|d| d.state().address == address) {
+            Some(device) => device.transaction(address, operations),
+            None => Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for MockPCF8591 {
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+        I2c::transaction(self, address, operations)
+    }
+}
@@ -0,0 +1,48 @@
+//! Combination of several conversions of one input
+
+/// Number of conversions combined into one reading
+///
+/// All conversions of a reading are returned by burst reads of one input,
+/// without auto-increment, the converter converting again on every byte.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Oversampling {
+    /// A single conversion
+    None,
+    /// Mean of given number of conversions
+    Average(u16),
+    /// Sum of `4^bits` conversions shifted right by `bits`, i.e. a reading of
+    /// `8 + bits` bits with uncorrelated noise. `bits` is at most 8.
+    Decimate(u8),
+}
+
+impl Default for Oversampling {
+    /// A single conversion
+    fn default() -> Oversampling {
+        Oversampling::None
+    }
+}
+
+impl Oversampling {
+    /// Maximum number of extra bits of `Decimate`
+    pub const MAX_BITS: u8 = 8;
+
+    /// Number of conversions to read
+    pub fn samples(&self) -> u32 {
+        match *self {
+            Oversampling::None => 1,
+            Oversampling::Average(n) => n.max(1) as u32,
+            Oversampling::Decimate(bits) => 1 << (2 * bits.min(Self::MAX_BITS)),
+        }
+    }
+
+    /// Combines the sum of `samples()` codes into a code, with a fractional part
+    pub fn code(&self, sum: u32) -> f64 {
+        match *self {
+            Oversampling::Decimate(bits) => {
+                let bits = bits.min(Self::MAX_BITS);
+                (sum >> bits) as f64 / (1u32 << bits) as f64
+            }
+            _ => sum as f64 / self.samples() as f64,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Stored configuration of a converter
+//!
+//! A `Profile` holds everything needed to rebuild a calibrated converter:
+//! address, reference voltages, wiring of the inputs and calibrations. With
+//! the `serde` feature it can be (de)serialized, the `toml` and `json`
+//! features adding helpers to load and save it as a file.
+//!
+//! ```rust,should_panic
+//! # #[cfg(feature = "toml")] {
+//! use pcf8591::PCF8591;
+//! use pcf8591::profile::Profile;
+//!
+//! let profile = Profile::load_toml("/etc/pcf8591.toml").unwrap();
+//! let mut converter = PCF8591::open("/dev/i2c-1", &profile).unwrap();
+//! # }
+//! # #[cfg(not(feature = "toml"))] panic!();
+//! ```
+//!
+//! The calibrations, when given, list the four inputs in order, their
+//! omitted fields keeping their default:
+//!
+//! ```toml
+//! address = 72
+//! v_ref = 3.3
+//! v_agnd = 0.0
+//! input_mode = "SingleEnded"
+//!
+//! # AIN0, behind a 1:2 divider
+//! [[calibrations]]
+//! offset = 0.0
+//! gain = 1.0
+//! divider = 2.0
+//!
+//! # AIN1 to AIN3
+//! [[calibrations]]
+//! [[calibrations]]
+//! [[calibrations]]
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(any(feature = "toml", feature = "json"))]
+use std::{fmt, fs, io, path::Path};
+
+use crate::{Address, Calibration, InputMode};
+
+/// Configuration of a converter
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Profile {
+    /// Address of the converter
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub address: Address,
+    /// Reference voltage
+    pub v_ref: f64,
+    /// Analog ground voltage
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub v_agnd: f64,
+    /// Input mode the board is wired for, see `InputMode::channels`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub input_mode: InputMode,
+    /// Calibration of each input pin
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub calibrations: [Calibration; 4],
+    /// Calibration of the analog output
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub output_calibration: Calibration,
+}
+
+impl Profile {
+    /// Creates a profile with default address, single-ended inputs and no calibration
+    pub fn new(v_ref: f64) -> Profile {
+        Profile {
+            address: Address::default(),
+            v_ref,
+            v_agnd: 0.,
+            input_mode: InputMode::default(),
+            calibrations: [Calibration::default(); 4],
+            output_calibration: Calibration::default(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.value())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+        let address = u16::deserialize(deserializer)?;
+        Address::new(address).ok_or_else(|| {
+            de::Error::custom(format_args!("invalid PCF8591 address: {:#04x}", address))
+        })
+    }
+}
+
+/// Errors returned when loading or saving a `Profile`
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProfileError {
+    /// Error reading or writing the file
+    Io(io::Error),
+    /// Invalid TOML profile
+    #[cfg(feature = "toml")]
+    TomlDe(toml::de::Error),
+    /// Profile cannot be written as TOML
+    #[cfg(feature = "toml")]
+    TomlSer(toml::ser::Error),
+    /// Invalid JSON profile
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+impl From<io::Error> for ProfileError {
+    fn from(e: io::Error) -> Self {
+        ProfileError::Io(e)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::de::Error> for ProfileError {
+    fn from(e: toml::de::Error) -> Self {
+        ProfileError::TomlDe(e)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::ser::Error> for ProfileError {
+    fn from(e: toml::ser::Error) -> Self {
+        ProfileError::TomlSer(e)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for ProfileError {
+    fn from(e: serde_json::Error) -> Self {
+        ProfileError::Json(e)
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProfileError::Io(ref e) => write!(f, "profile file error: {}", e),
+            #[cfg(feature = "toml")]
+            ProfileError::TomlDe(ref e) => write!(f, "invalid TOML profile: {}", e),
+            #[cfg(feature = "toml")]
+            ProfileError::TomlSer(ref e) => write!(f, "cannot write TOML profile: {}", e),
+            #[cfg(feature = "json")]
+            ProfileError::Json(ref e) => write!(f, "JSON profile error: {}", e),
+        }
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+impl ::std::error::Error for ProfileError {
+    fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+        match *self {
+            ProfileError::Io(ref e) => Some(e),
+            #[cfg(feature = "toml")]
+            ProfileError::TomlDe(ref e) => Some(e),
+            #[cfg(feature = "toml")]
+            ProfileError::TomlSer(ref e) => Some(e),
+            #[cfg(feature = "json")]
+            ProfileError::Json(ref e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl Profile {
+    /// Parses a TOML profile
+    pub fn from_toml(s: &str) -> Result<Profile, ProfileError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// Writes the profile as TOML
+    pub fn to_toml(&self) -> Result<String, ProfileError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Loads a TOML profile file
+    pub fn load_toml<P: AsRef<Path>>(path: P) -> Result<Profile, ProfileError> {
+        Profile::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the profile as a TOML file
+    pub fn save_toml<P: AsRef<Path>>(&self, path: P) -> Result<(), ProfileError> {
+        Ok(fs::write(path, self.to_toml()?)?)
+    }
+}
+
+#[cfg(feature = "json")]
+impl Profile {
+    /// Parses a JSON profile
+    pub fn from_json(s: &str) -> Result<Profile, ProfileError> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    /// Writes the profile as pretty printed JSON
+    pub fn to_json(&self) -> Result<String, ProfileError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Loads a JSON profile file
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Profile, ProfileError> {
+        Profile::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the profile as a JSON file
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<(), ProfileError> {
+        Ok(fs::write(path, self.to_json()?)?)
+    }
+}
@@ -0,0 +1,137 @@
+//! Fixed-rate sampling of the converter inputs
+//!
+//! Inputs are read on a monotonic deadline schedule (`start + n * period`) so
+//! that the sampling rate does not drift with the time spent on the bus.
+//!
+//! ```rust,should_panic
+//! use pcf8591::{Address, PCF8591, Pin};
+//!
+//! let mut converter = PCF8591::new("/dev/i2c-1", Address::default(), 3.3).unwrap();
+//!
+//! // 10 scans of AIN0 and AIN1 per second
+//! for scan in converter.sampler(&[Pin::AIN0.into(), Pin::AIN1.into()], 10.) {
+//!     let scan = scan.unwrap();
+//!     if scan.overruns > 0 {
+//!         println!("missed {} scans", scan.overruns);
+//!     }
+//!     for sample in scan.samples {
+//!         println!("{:?} at {:?}: {}V", sample.channel, sample.timestamp, sample.voltage);
+//!     }
+//! }
+//! ```
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use embedded_hal::i2c::I2c;
+
+use crate::{Channel, Result, PCF8591};
+
+/// A single conversion result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// The input which was converted
+    pub channel: Channel,
+    /// Raw code, in two's complement for differential inputs, nearest code
+    /// of the mean for oversampled pins
+    pub code: u8,
+    /// Converted voltage
+    pub voltage: f64,
+    /// Start of the conversion
+    ///
+    /// As the converter returns the result of the previous conversion, this is
+    /// the time of the read preceding the one which returned the sample.
+    pub timestamp: Instant,
+}
+
+impl Sample {
+    pub(crate) fn new(channel: Channel, code: u8, voltage: f64, timestamp: Instant) -> Sample {
+        Sample { channel, code, voltage, timestamp }
+    }
+}
+
+/// Samples of all sampled channels at one deadline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    /// Samples, in the order of the sampled channels
+    pub samples: Vec<Sample>,
+    /// Number of deadlines skipped since the previous scan, this scan being
+    /// late because the bus or the caller could not keep up with the rate
+    pub overruns: u32,
+}
+
+/// A monotonic deadline schedule
+pub(crate) struct Schedule {
+    period: Duration,
+    deadline: Instant,
+}
+
+impl Schedule {
+    pub(crate) fn new(rate: f64) -> Schedule {
+        assert!(rate > 0. && rate.is_finite(), "invalid sampling rate {}", rate);
+        Schedule {
+            period: Duration::from_secs_f64(1. / rate),
+            deadline: Instant::now(),
+        }
+    }
+
+    /// Time left before the next deadline, if any
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        self.deadline.checked_duration_since(Instant::now())
+    }
+
+    /// Skips the deadlines already passed but the last one, returns their number
+    pub(crate) fn catch_up(&mut self) -> u32 {
+        let now = Instant::now();
+        if self.deadline >= now {
+            return 0;
+        }
+        let late = now - self.deadline;
+        let missed = (late.as_nanos() / self.period.as_nanos()) as u32;
+        self.deadline += self.period * missed;
+        missed
+    }
+
+    /// Moves to the next deadline
+    pub(crate) fn advance(&mut self) {
+        self.deadline += self.period;
+    }
+}
+
+/// An endless iterator reading a set of channels at a fixed rate
+///
+/// Each call to `next` sleeps until the next deadline then reads all channels.
+/// Bus errors are yielded without stopping the iteration.
+pub struct Sampler<'a, I2C> {
+    converter: &'a mut PCF8591<I2C>,
+    channels: Vec<Channel>,
+    schedule: Schedule,
+}
+
+impl<'a, I2C: I2c> Sampler<'a, I2C> {
+    pub(crate) fn new(converter: &'a mut PCF8591<I2C>, channels: &[Channel], rate: f64) -> Sampler<'a, I2C> {
+        Sampler {
+            converter,
+            channels: channels.to_vec(),
+            schedule: Schedule::new(rate),
+        }
+    }
+}
+
+impl<'a, I2C: I2c> Iterator for Sampler<'a, I2C> {
+    type Item = Result<Scan, I2C::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(remaining) = self.schedule.remaining() {
+            thread::sleep(remaining);
+        }
+        let overruns = self.schedule.catch_up();
+        let converter = &mut *self.converter;
+        let samples = self.channels
+            .iter()
+            .map(|c| converter.sample(*c))
+            .collect::<Result<Vec<_>, _>>();
+        self.schedule.advance();
+        Some(samples.map(|samples| Scan { samples, overruns }))
+    }
+}
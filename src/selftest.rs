@@ -0,0 +1,210 @@
+//! Closed-loop self-test, reading back the analog output on an input pin
+//!
+//! With AOUT wired to one input, `PCF8591::self_test` sweeps all DAC codes,
+//! reads each one back and fits a line through the readings. The `Report`
+//! gives the offset, gain error, linearity and missing codes of the whole
+//! loop, i.e. of the DAC and the ADC together.
+//!
+//! ```rust,should_panic
+//! # #[cfg(feature = "std")] {
+//! use pcf8591::{Address, Error, PCF8591, Pin};
+//! use pcf8591::selftest::SelfTest;
+//!
+//! let mut converter = PCF8591::new("/dev/i2c-1", Address::default(), 3.3).unwrap();
+//! match converter.self_test(&SelfTest::new(Pin::AIN2)) {
+//!     Ok(report) => println!("board ok: {}", report),
+//!     Err(Error::SelfTestFailed(report)) => println!("board failed: {}", report),
+//!     Err(e) => println!("bus error: {}", e),
+//! }
+//! # }
+//! # #[cfg(not(feature = "std"))] panic!();
+//! ```
+
+use core::fmt;
+
+use crate::Pin;
+
+/// Maximum deviations accepted by a self-test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerances {
+    /// Maximum absolute offset, in LSB
+    pub offset: f64,
+    /// Maximum absolute gain error, as a fraction of the ideal gain
+    pub gain_error: f64,
+    /// Maximum integral non-linearity, in LSB
+    pub inl: f64,
+    /// Maximum differential non-linearity, in LSB
+    pub dnl: f64,
+    /// Maximum number of missing codes
+    pub missing_codes: usize,
+}
+
+impl Default for Tolerances {
+    /// Datasheet limits of the DAC and the ADC, added
+    fn default() -> Tolerances {
+        Tolerances {
+            offset: 3.,
+            gain_error: 0.02,
+            inl: 2.,
+            dnl: 1.5,
+            missing_codes: 0,
+        }
+    }
+}
+
+/// Configuration of a self-test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelfTest {
+    /// Input pin wired to AOUT
+    pub pin: Pin,
+    /// Number of readings averaged per DAC code, at least 1
+    pub samples: u8,
+    /// Limits of a passing test
+    pub tolerances: Tolerances,
+}
+
+impl SelfTest {
+    /// Creates a self-test reading back AOUT on `pin`, with 4 readings per
+    /// code and the default tolerances
+    pub fn new(pin: Pin) -> SelfTest {
+        SelfTest {
+            pin,
+            samples: 4,
+            tolerances: Tolerances::default(),
+        }
+    }
+}
+
+/// Result of a self-test
+///
+/// All values are in ADC LSB, relative to the DAC code, saturated readings
+/// being excluded from the fit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    /// Offset of the fitted line, in LSB
+    pub offset: f64,
+    /// Gain error of the fitted line, as a fraction of the ideal gain
+    pub gain_error: f64,
+    /// Integral non-linearity, maximum distance to the fitted line, in LSB
+    pub inl: f64,
+    /// Differential non-linearity, maximum step error, in LSB
+    pub dnl: f64,
+    /// Codes never read between the lowest and the highest reading
+    missing: [u32; 8],
+}
+
+impl Report {
+    /// Iterates over the missing codes
+    pub fn missing_codes(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255u8).filter(move |&c| self.missing[c as usize / 32] & (1 << (c % 32)) != 0)
+    }
+
+    /// Number of missing codes
+    pub fn missing_count(&self) -> usize {
+        self.missing.iter().map(|m| m.count_ones() as usize).sum()
+    }
+
+    /// Returns true if the report is within given tolerances
+    pub fn passes(&self, tolerances: &Tolerances) -> bool {
+        self.offset.abs() <= tolerances.offset
+            && self.gain_error.abs() <= tolerances.gain_error
+            && self.inl <= tolerances.inl
+            && self.dnl <= tolerances.dnl
+            && self.missing_count() <= tolerances.missing_codes
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "offset {:.2} LSB, gain error {:.2}%, INL {:.2} LSB, DNL {:.2} LSB, {} missing codes",
+            self.offset,
+            self.gain_error * 100.,
+            self.inl,
+            self.dnl,
+            self.missing_count()
+        )
+    }
+}
+
+/// Readings of a DAC sweep
+pub(crate) struct Sweep {
+    means: [f64; 256],
+    seen: [u32; 8],
+}
+
+impl Sweep {
+    pub(crate) fn new() -> Sweep {
+        Sweep {
+            means: [0.; 256],
+            seen: [0; 8],
+        }
+    }
+
+    /// Records the readings of one DAC code
+    pub(crate) fn record(&mut self, code: u8, readings: &[u8]) {
+        let sum = readings.iter().map(|&r| r as u32).sum::<u32>();
+        self.means[code as usize] = sum as f64 / readings.len() as f64;
+        for &r in readings {
+            self.seen[r as usize / 32] |= 1 << (r % 32);
+        }
+    }
+
+    /// Codes of the unsaturated readings
+    fn linear(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.means
+            .iter()
+            .enumerate()
+            .map(|(x, &y)| (x as f64, y))
+            .filter(|&(_, y)| y > 0.5 && y < 254.5)
+    }
+
+    pub(crate) fn report(&self) -> Report {
+        // least squares fit
+        let (mut n, mut sx, mut sy, mut sxx, mut sxy) = (0., 0., 0., 0., 0.);
+        for (x, y) in self.linear() {
+            n += 1.;
+            sx += x;
+            sy += y;
+            sxx += x * x;
+            sxy += x * y;
+        }
+        let gain = (n * sxy - sx * sy) / (n * sxx - sx * sx);
+        let offset = (sy - gain * sx) / n;
+
+        let mut inl: f64 = 0.;
+        let mut dnl: f64 = 0.;
+        let mut previous: Option<(f64, f64)> = None;
+        for (x, y) in self.linear() {
+            inl = inl.max((y - gain * x - offset).abs());
+            if let Some((px, py)) = previous {
+                dnl = dnl.max(((y - py) - gain * (x - px)).abs());
+            }
+            previous = Some((x, y));
+        }
+
+        let lowest = (0..256).find(|&c| self.is_seen(c)).unwrap_or(0);
+        let highest = (0..256).rev().find(|&c| self.is_seen(c)).unwrap_or(0);
+        let mut missing = [0; 8];
+        for c in lowest..highest {
+            if !self.is_seen(c) {
+                missing[c / 32] |= 1 << (c % 32);
+            }
+        }
+
+        // a sweep without enough unsaturated readings fails every tolerance
+        let nan_to_inf = |v: f64| if v.is_nan() { f64::INFINITY } else { v };
+        Report {
+            offset: nan_to_inf(offset),
+            gain_error: nan_to_inf(gain - 1.),
+            inl: nan_to_inf(inl),
+            dnl: nan_to_inf(dnl),
+            missing,
+        }
+    }
+
+    fn is_seen(&self, code: usize) -> bool {
+        self.seen[code / 32] & (1 << (code % 32)) != 0
+    }
+}
@@ -0,0 +1,320 @@
+//! Conversion of readings into physical units
+//!
+//! A `Conversion` maps the code and the calibrated voltage of a reading into
+//! a physical quantity, see `PCF8591::read_sensor`. Lookup tables and
+//! polynomials cover any sensor, thermistors and photoresistors wired in a
+//! resistor divider have dedicated models, and `breakout` describes the
+//! sensors of the common PCF8591 breakout modules.
+//!
+//! ```rust,should_panic
+//! # #[cfg(feature = "std")] {
+//! use pcf8591::{Address, PCF8591};
+//! use pcf8591::sensor::{breakout, Input, Lookup};
+//!
+//! let mut converter = PCF8591::new("/dev/i2c-1", Address::default(), 3.3).unwrap();
+//!
+//! let celsius = converter.read_sensor(breakout::THERMISTOR, &breakout::thermistor(3.3)).unwrap();
+//!
+//! // a humidity sensor, in %, from its datasheet table
+//! let table = [(0.8, 20.), (1.6, 50.), (2.4, 80.)];
+//! let humidity = Lookup::new(Input::Voltage, &table).unwrap();
+//! let rh = converter.read_sensor(breakout::AIN2, &humidity).unwrap();
+//! # }
+//! # #[cfg(not(feature = "std"))] panic!();
+//! ```
+
+/// Offset between the Celsius and the Kelvin scales
+#[cfg(feature = "std")]
+const KELVIN: f64 = 273.15;
+
+/// Conversion of a reading into a physical quantity
+pub trait Conversion {
+    /// Converts a reading, given as its raw code and its calibrated voltage
+    fn convert(&self, code: u8, voltage: f64) -> f64;
+}
+
+/// Any function of the voltage is a conversion
+impl<F: Fn(f64) -> f64> Conversion for F {
+    fn convert(&self, _code: u8, voltage: f64) -> f64 {
+        self(voltage)
+    }
+}
+
+/// Value used as input of a lookup table or a polynomial
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    /// The raw code, `0.` to `255.`
+    Code,
+    /// The calibrated voltage
+    Voltage,
+}
+
+impl Input {
+    fn value(&self, code: u8, voltage: f64) -> f64 {
+        match *self {
+            Input::Code => code as f64,
+            Input::Voltage => voltage,
+        }
+    }
+}
+
+/// A lookup table, linearly interpolated between its points
+#[derive(Debug, Clone, Copy)]
+pub struct Lookup<'a> {
+    input: Input,
+    points: &'a [(f64, f64)],
+}
+
+impl<'a> Lookup<'a> {
+    /// Creates a lookup table from `(input, output)` points
+    ///
+    /// Inputs below the first point or above the last one are clamped. Returns
+    /// `None` if there is no point or if inputs are not strictly increasing.
+    pub fn new(input: Input, points: &'a [(f64, f64)]) -> Option<Lookup<'a>> {
+        let increasing = points.windows(2).all(|w| w[0].0 < w[1].0);
+        if points.is_empty() || !increasing {
+            return None;
+        }
+        Some(Lookup { input, points })
+    }
+
+    /// Interpolates the output for given input
+    pub fn interpolate(&self, x: f64) -> f64 {
+        let i = self.points.partition_point(|p| p.0 < x);
+        if i == 0 {
+            return self.points[0].1;
+        }
+        if i == self.points.len() {
+            return self.points[i - 1].1;
+        }
+        let (x0, y0) = self.points[i - 1];
+        let (x1, y1) = self.points[i];
+        y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+    }
+}
+
+impl<'a> Conversion for Lookup<'a> {
+    fn convert(&self, code: u8, voltage: f64) -> f64 {
+        self.interpolate(self.input.value(code, voltage))
+    }
+}
+
+/// A polynomial, `c[0] + c[1] * x + c[2] * x^2 + ...`
+#[derive(Debug, Clone, Copy)]
+pub struct Polynomial<'a> {
+    input: Input,
+    coefficients: &'a [f64],
+}
+
+impl<'a> Polynomial<'a> {
+    /// Creates a polynomial from its coefficients, constant term first
+    pub fn new(input: Input, coefficients: &'a [f64]) -> Polynomial<'a> {
+        Polynomial { input, coefficients }
+    }
+
+    /// Evaluates the polynomial
+    pub fn evaluate(&self, x: f64) -> f64 {
+        self.coefficients.iter().rev().fold(0., |acc, c| acc * x + c)
+    }
+}
+
+impl<'a> Conversion for Polynomial<'a> {
+    fn convert(&self, code: u8, voltage: f64) -> f64 {
+        self.evaluate(self.input.value(code, voltage))
+    }
+}
+
+/// Fraction of the supply voltage, `0.` to `1.`, e.g. the position of a potentiometer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ratio {
+    /// Supply voltage of the sensor
+    pub v_supply: f64,
+}
+
+impl Conversion for Ratio {
+    fn convert(&self, _code: u8, voltage: f64) -> f64 {
+        voltage / self.v_supply
+    }
+}
+
+/// A resistive sensor in series with a fixed resistor across the supply
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divider {
+    /// Fixed resistance, in ohms
+    pub r_fixed: f64,
+    /// Supply voltage of the divider
+    pub v_supply: f64,
+    /// True if the sensor is between the input and the supply, false if it is
+    /// between the input and the ground
+    pub high_side: bool,
+}
+
+impl Divider {
+    /// Sensor between the input and the ground, fixed resistor to the supply
+    pub fn low_side(r_fixed: f64, v_supply: f64) -> Divider {
+        Divider { r_fixed, v_supply, high_side: false }
+    }
+
+    /// Sensor between the input and the supply, fixed resistor to the ground
+    pub fn high_side(r_fixed: f64, v_supply: f64) -> Divider {
+        Divider { r_fixed, v_supply, high_side: true }
+    }
+
+    /// Resistance of the sensor, in ohms, for given input voltage
+    pub fn resistance(&self, voltage: f64) -> f64 {
+        if self.high_side {
+            self.r_fixed * (self.v_supply - voltage) / voltage
+        } else {
+            self.r_fixed * voltage / (self.v_supply - voltage)
+        }
+    }
+}
+
+/// NTC thermistor Beta model, in Celsius
+///
+/// `1 / T = 1 / T0 + ln(R / R0) / Beta`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Beta {
+    /// Resistance at `t0`, in ohms
+    pub r0: f64,
+    /// Reference temperature, in Celsius, typically 25°C
+    pub t0: f64,
+    /// Beta coefficient, in Kelvin
+    pub beta: f64,
+    /// Wiring of the thermistor
+    pub divider: Divider,
+}
+
+#[cfg(feature = "std")]
+impl Beta {
+    /// Temperature, in Celsius, for given thermistor resistance
+    pub fn temperature(&self, resistance: f64) -> f64 {
+        1. / (1. / (self.t0 + KELVIN) + (resistance / self.r0).ln() / self.beta) - KELVIN
+    }
+}
+
+#[cfg(feature = "std")]
+impl Conversion for Beta {
+    fn convert(&self, _code: u8, voltage: f64) -> f64 {
+        self.temperature(self.divider.resistance(voltage))
+    }
+}
+
+/// NTC thermistor Steinhart–Hart model, in Celsius
+///
+/// `1 / T = A + B * ln(R) + C * ln(R)^3`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteinhartHart {
+    /// A coefficient
+    pub a: f64,
+    /// B coefficient
+    pub b: f64,
+    /// C coefficient
+    pub c: f64,
+    /// Wiring of the thermistor
+    pub divider: Divider,
+}
+
+#[cfg(feature = "std")]
+impl SteinhartHart {
+    /// Temperature, in Celsius, for given thermistor resistance
+    pub fn temperature(&self, resistance: f64) -> f64 {
+        let ln_r = resistance.ln();
+        1. / (self.a + self.b * ln_r + self.c * ln_r.powi(3)) - KELVIN
+    }
+}
+
+#[cfg(feature = "std")]
+impl Conversion for SteinhartHart {
+    fn convert(&self, _code: u8, voltage: f64) -> f64 {
+        self.temperature(self.divider.resistance(voltage))
+    }
+}
+
+/// Photoresistor power law model, in lux
+///
+/// `lux = 10 * (R10 / R) ^ (1 / gamma)`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ldr {
+    /// Resistance at 10 lux, in ohms
+    pub r10: f64,
+    /// Slope of the log(R) / log(lux) characteristic
+    pub gamma: f64,
+    /// Wiring of the photoresistor
+    pub divider: Divider,
+}
+
+#[cfg(feature = "std")]
+impl Ldr {
+    /// Illuminance, in lux, for given photoresistor resistance
+    pub fn illuminance(&self, resistance: f64) -> f64 {
+        10. * (self.r10 / resistance).powf(1. / self.gamma)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Conversion for Ldr {
+    fn convert(&self, _code: u8, voltage: f64) -> f64 {
+        self.illuminance(self.divider.resistance(voltage))
+    }
+}
+
+/// Sensors of the common PCF8591 breakout modules (YL-40 and alike)
+///
+/// With their jumpers set, AIN0 reads a photoresistor, AIN1 a thermistor and
+/// AIN3 a potentiometer, the photoresistor and the thermistor being pulled up
+/// to the supply by 10kΩ resistors. AIN2 is left free on the header.
+pub mod breakout {
+    #[cfg(feature = "std")]
+    use super::{Beta, Divider, Ldr};
+    use super::Ratio;
+    use crate::Pin;
+
+    /// Input of the photoresistor
+    pub const LDR: Pin = Pin::AIN0;
+    /// Input of the thermistor
+    pub const THERMISTOR: Pin = Pin::AIN1;
+    /// Free input, on the header
+    pub const AIN2: Pin = Pin::AIN2;
+    /// Input of the potentiometer
+    pub const POT: Pin = Pin::AIN3;
+
+    /// Pull-up resistance of the photoresistor and of the thermistor
+    #[cfg(feature = "std")]
+    const R_PULL_UP: f64 = 10_000.;
+
+    /// The 10kΩ NTC thermistor (Beta 3950K), in Celsius
+    ///
+    /// - `v_supply`: supply voltage of the module
+    #[cfg(feature = "std")]
+    pub fn thermistor(v_supply: f64) -> Beta {
+        Beta {
+            r0: 10_000.,
+            t0: 25.,
+            beta: 3950.,
+            divider: Divider::low_side(R_PULL_UP, v_supply),
+        }
+    }
+
+    /// The GL5528-like photoresistor, in lux, only giving an order of magnitude
+    ///
+    /// - `v_supply`: supply voltage of the module
+    #[cfg(feature = "std")]
+    pub fn ldr(v_supply: f64) -> Ldr {
+        Ldr {
+            r10: 15_000.,
+            gamma: 0.7,
+            divider: Divider::low_side(R_PULL_UP, v_supply),
+        }
+    }
+
+    /// The potentiometer position, `0.` to `1.`
+    ///
+    /// - `v_supply`: supply voltage of the module
+    pub fn pot(v_supply: f64) -> Ratio {
+        Ratio { v_supply }
+    }
+}
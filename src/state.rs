@@ -0,0 +1,229 @@
+//! Control byte and conversion logic shared by the blocking and async drivers
+
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::profile::Profile;
+use crate::{Address, Calibration, Error, InputMode, Oversampling, Pin};
+#[cfg(feature = "std")]
+use crate::Channel;
+
+/// Analog output enable flag of the control byte
+const ANALOG_OUTPUT_ENABLE: u8 = 0x40;
+
+/// Auto-increment flag of the control byte
+const AUTO_INCREMENT: u8 = 0x04;
+
+/// Builds the control byte, as per Fig 4.
+fn control_byte(output_enabled: bool, (mode, channel): (InputMode, u8)) -> u8 {
+    let output = if output_enabled { ANALOG_OUTPUT_ENABLE } else { 0 };
+    output | mode.bits() | channel
+}
+
+/// Rounds a code, already checked to be in `0..=256`, to the nearest byte
+fn round_code(code: f64) -> u8 {
+    (code + 0.5).min(255.) as u8
+}
+
+/// Number of samples read per burst transaction, without the stale leading byte
+pub(crate) const BURST_CHUNK: usize = 32;
+
+/// Number of voltages converted at once by streamed DAC writes
+pub(crate) const WRITE_CHUNK: usize = 64;
+
+/// Converter state, independent of the I2C bus
+pub(crate) struct State {
+    pub(crate) address: Address,
+    /// Last control byte sent, if the next read returns a conversion of its channel
+    pub(crate) control: Option<u8>,
+    pub(crate) output_enabled: bool,
+    /// Last code written to the DAC, 0 at power-on
+    pub(crate) dac: u8,
+    pub(crate) v_ref: f64,
+    pub(crate) v_agnd: f64,
+    /// Input mode the board is wired for
+    pub(crate) input_mode: InputMode,
+    /// Calibration of each input pin
+    pub(crate) calibrations: [Calibration; 4],
+    /// Calibration of the analog output
+    pub(crate) output_calibration: Calibration,
+    /// Oversampling of each input pin
+    pub(crate) oversampling: [Oversampling; 4],
+    /// Start of the conversion held in the data register
+    #[cfg(feature = "std")]
+    pub(crate) converted_at: Option<Instant>,
+    /// Start of the conversion returned by the last read
+    #[cfg(feature = "std")]
+    pub(crate) acquired_at: Option<Instant>,
+}
+
+impl State {
+    pub(crate) fn new(address: Address, v_ref: f64) -> State {
+        State {
+            address,
+            control: None,
+            output_enabled: true,
+            dac: 0,
+            v_ref,
+            v_agnd: 0.,
+            input_mode: InputMode::default(),
+            calibrations: [Calibration::default(); 4],
+            output_calibration: Calibration::default(),
+            oversampling: [Oversampling::default(); 4],
+            #[cfg(feature = "std")]
+            converted_at: None,
+            #[cfg(feature = "std")]
+            acquired_at: None,
+        }
+    }
+
+    pub(crate) fn from_profile(profile: &Profile) -> State {
+        State {
+            v_agnd: profile.v_agnd,
+            input_mode: profile.input_mode,
+            calibrations: profile.calibrations,
+            output_calibration: profile.output_calibration,
+            ..State::new(profile.address, profile.v_ref)
+        }
+    }
+
+    /// Configuration of the converter
+    pub(crate) fn profile(&self) -> Profile {
+        Profile {
+            address: self.address,
+            v_agnd: self.v_agnd,
+            input_mode: self.input_mode,
+            calibrations: self.calibrations,
+            output_calibration: self.output_calibration,
+            ..Profile::new(self.v_ref)
+        }
+    }
+
+    /// Records the start of a read transaction, which returns the previous
+    /// conversion while starting a new one
+    pub(crate) fn start_read(&mut self) {
+        #[cfg(feature = "std")]
+        {
+            self.acquired_at = self.converted_at.replace(Instant::now());
+        }
+    }
+
+    /// Control byte to read given channel
+    pub(crate) fn read_control(&self, channel: (InputMode, u8)) -> u8 {
+        control_byte(self.output_enabled, channel)
+    }
+
+    /// Control byte to read all four input pins with auto-increment
+    pub(crate) fn scan_control(&self) -> u8 {
+        control_byte(self.output_enabled, (InputMode::SingleEnded, 0)) | AUTO_INCREMENT
+    }
+
+    /// Control byte preceding DAC data bytes
+    pub(crate) fn write_control(&self) -> u8 {
+        control_byte(self.output_enabled, (InputMode::SingleEnded, 0))
+    }
+
+    /// Control byte switching the analog output on or off
+    pub(crate) fn output_control(&self, enabled: bool) -> u8 {
+        control_byte(enabled, (InputMode::SingleEnded, 0))
+    }
+
+    /// Voltage of one least significant bit
+    pub(crate) fn v_lsb(&self) -> f64 {
+        (self.v_ref - self.v_agnd) / 256.
+    }
+
+    /// Converts a single-ended code into the corresponding voltage, as per Fig 9.
+    pub(crate) fn code_to_voltage(&self, code: u8) -> f64 {
+        self.v_agnd + code as f64 * self.v_lsb()
+    }
+
+    /// Converts a single-ended code read on `pin` into the calibrated voltage
+    pub(crate) fn pin_voltage(&self, pin: Pin, code: u8) -> f64 {
+        self.calibrations[pin as usize].apply(self.code_to_voltage(code))
+    }
+
+    /// Converts an oversampled code, with a fractional part, into the calibrated voltage
+    pub(crate) fn oversampled_voltage(&self, pin: Pin, code: f64) -> f64 {
+        self.calibrations[pin as usize].apply(self.v_agnd + code * self.v_lsb())
+    }
+
+    /// Nearest code and calibrated voltage of an oversampled reading
+    pub(crate) fn oversampled_reading(&self, pin: Pin, code: f64) -> (u8, f64) {
+        (round_code(code), self.oversampled_voltage(pin, code))
+    }
+
+    /// Converts the codes of a scan of the four input pins into calibrated voltages
+    pub(crate) fn scan_voltages(&self, codes: [u8; 4]) -> [f64; 4] {
+        let pins = [Pin::AIN0, Pin::AIN1, Pin::AIN2, Pin::AIN3];
+        pins.map(|pin| self.pin_voltage(pin, codes[pin as usize]))
+    }
+
+    /// Converts a differential code into the corresponding voltage, as per Fig 10.
+    pub(crate) fn diff_to_voltage(&self, code: i8) -> f64 {
+        code as f64 * self.v_lsb()
+    }
+
+    /// Converts a code read on `channel` into the corresponding voltage
+    #[cfg(feature = "std")]
+    pub(crate) fn channel_voltage(&self, channel: Channel, code: u8) -> f64 {
+        match channel {
+            Channel::Single(pin) => self.pin_voltage(pin, code),
+            Channel::Differential(_) => self.diff_to_voltage(code as i8),
+        }
+    }
+
+    /// Converts a calibrated output voltage into the exact, non rounded, DAC code
+    fn output_code(&self, v_out: f64) -> f64 {
+        (self.output_calibration.invert(v_out) - self.v_agnd) / self.v_lsb()
+    }
+
+    /// Converts a voltage into the nearest DAC code, as per Fig 8.
+    pub(crate) fn voltage_to_code<E>(&self, v_out: f64) -> Result<u8, Error<E>> {
+        let code = self.output_code(v_out);
+        if !(0. ..=256.).contains(&code) {
+            return Err(Error::VoltageOutOfRange(v_out));
+        }
+        Ok(round_code(code))
+    }
+
+    /// Checks that all voltages can be output, before streaming them
+    pub(crate) fn check_voltages<E>(&self, voltages: &[f64]) -> Result<(), Error<E>> {
+        voltages.iter().try_for_each(|&v| self.voltage_to_code(v).map(|_| ()))
+    }
+
+    /// Converts a voltage into the nearest DAC code, saturating out of range voltages
+    pub(crate) fn voltage_to_code_saturating<E>(&self, v_out: f64) -> Result<u8, Error<E>> {
+        let code = self.output_code(v_out);
+        if code.is_nan() {
+            return Err(Error::VoltageOutOfRange(v_out));
+        }
+        Ok(round_code(code.clamp(0., 256.)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiffPin, Pin};
+
+    #[test]
+    fn control_bytes() {
+        assert_eq!(control_byte(true, Pin::AIN0.channel()), 0x40);
+        assert_eq!(control_byte(true, Pin::AIN3.channel()), 0x43);
+        assert_eq!(control_byte(false, Pin::AIN2.channel()), 0x02);
+        assert_eq!(control_byte(true, DiffPin::AIN0_AIN3.channel()), 0x50);
+        assert_eq!(control_byte(true, DiffPin::AIN2_AIN3.channel()), 0x52);
+        assert_eq!(control_byte(true, DiffPin::AIN0_AIN1.channel()), 0x70);
+        assert_eq!(control_byte(true, (InputMode::Mixed, 2)), 0x62);
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(round_code(0.), 0);
+        assert_eq!(round_code(0.49), 0);
+        assert_eq!(round_code(0.5), 1);
+        assert_eq!(round_code(254.6), 255);
+        assert_eq!(round_code(256.), 255);
+    }
+}
@@ -0,0 +1,197 @@
+//! Periodic signals played on the analog output
+//!
+//! A `Generator` plays a `Waveform` at a fixed update rate, sending several
+//! DAC values per write transaction. It can run in the current thread until
+//! a stop flag is set, or be spawned in its own thread and controlled through
+//! a `Player`.
+//!
+//! ```rust,should_panic
+//! use pcf8591::{Address, PCF8591};
+//! use pcf8591::waveform::{Generator, Shape, Waveform};
+//! use std::thread;
+//! use std::time::Duration;
+//!
+//! let converter = PCF8591::new("/dev/i2c-1", Address::default(), 3.3).unwrap();
+//!
+//! // 50Hz sine between 0.5V and 2.5V, updated 2000 times per second
+//! let sine = Waveform::new(Shape::Sine, 50., 0.5, 2.5);
+//! let player = Generator::new(sine, 2000.).spawn(converter);
+//!
+//! thread::sleep(Duration::from_secs(10));
+//! let (converter, result) = player.stop();
+//! result.unwrap();
+//! ```
+
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use embedded_hal::i2c::{ErrorType, I2c};
+
+use crate::sampler::Schedule;
+use crate::{Result, PCF8591};
+
+/// Shape of one period of a waveform
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// Sine
+    Sine,
+    /// Triangle, rising on the first half period
+    Triangle,
+    /// Sawtooth, rising over the whole period
+    Sawtooth,
+    /// Square, high for given fraction of the period (`0.5` for a symmetric square)
+    Square(f64),
+    /// Arbitrary samples of one period, from `0.` (low level) to `1.` (high level)
+    Table(Vec<f64>),
+}
+
+/// A periodic signal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+    /// Shape of one period
+    pub shape: Shape,
+    /// Frequency, in Hz
+    pub frequency: f64,
+    /// Low level, in volts
+    pub low: f64,
+    /// High level, in volts
+    pub high: f64,
+}
+
+impl Waveform {
+    /// Creates a new waveform oscillating between `low` and `high` volts
+    pub fn new(shape: Shape, frequency: f64, low: f64, high: f64) -> Waveform {
+        Waveform { shape, frequency, low, high }
+    }
+
+    /// Level, from `0.` to `1.`, at given phase, from `0.` to `1.`
+    fn level(&self, phase: f64) -> f64 {
+        match self.shape {
+            Shape::Sine => 0.5 - 0.5 * (2. * PI * phase).cos(),
+            Shape::Triangle if phase < 0.5 => 2. * phase,
+            Shape::Triangle => 2. - 2. * phase,
+            Shape::Sawtooth => phase,
+            Shape::Square(duty) => if phase < duty { 1. } else { 0. },
+            Shape::Table(ref samples) if samples.is_empty() => 0.,
+            Shape::Table(ref samples) => {
+                let i = (phase * samples.len() as f64) as usize;
+                samples[i.min(samples.len() - 1)]
+            }
+        }
+    }
+
+    /// Voltage at given time, in seconds
+    pub fn voltage(&self, t: f64) -> f64 {
+        let phase = (t * self.frequency).rem_euclid(1.);
+        self.low + (self.high - self.low) * self.level(phase)
+    }
+}
+
+/// Plays a waveform on the analog output at a fixed update rate
+///
+/// Updates are grouped in chunks, each chunk being sent in one write
+/// transaction. Chunks are scheduled at the update rate but the values of a
+/// chunk are output back to back at the speed of the bus (one value every 9
+/// clock periods), so large chunks trade timing accuracy for throughput.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generator {
+    waveform: Waveform,
+    rate: f64,
+    chunk: usize,
+}
+
+impl Generator {
+    /// Creates a generator updating the output `rate` times per second
+    ///
+    /// Chunks default to one millisecond of updates.
+    pub fn new(waveform: Waveform, rate: f64) -> Generator {
+        Generator {
+            waveform,
+            rate,
+            chunk: (rate / 1000.).ceil().max(1.) as usize,
+        }
+    }
+
+    /// Sets the number of updates sent per write transaction
+    pub fn chunk(mut self, updates: usize) -> Generator {
+        self.chunk = updates.max(1);
+        self
+    }
+
+    /// Plays the waveform until `stop` is set or a bus error occurs
+    ///
+    /// Voltages out of the DAC range are saturated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rate is not strictly positive and finite
+    pub fn run<I2C: I2c>(&self, converter: &mut PCF8591<I2C>, stop: &AtomicBool) -> Result<(), I2C::Error> {
+        let mut schedule = Schedule::new(self.rate / self.chunk as f64);
+        let mut codes = vec![0; self.chunk];
+        let mut n = 0u64;
+        while !stop.load(Ordering::Relaxed) {
+            // skipped chunks are dropped to keep the waveform in phase with time
+            n += schedule.catch_up() as u64 * self.chunk as u64;
+            for code in codes.iter_mut() {
+                let v = self.waveform.voltage(n as f64 / self.rate);
+                *code = converter.state.voltage_to_code_saturating(v)?;
+                n += 1;
+            }
+            if let Some(remaining) = schedule.remaining() {
+                thread::sleep(remaining);
+            }
+            converter.write_samples(&codes)?;
+            schedule.advance();
+        }
+        Ok(())
+    }
+
+    /// Plays the waveform in a new thread, see `run`
+    pub fn spawn<I2C>(self, mut converter: PCF8591<I2C>) -> Player<I2C>
+    where
+        I2C: I2c + Send + 'static,
+        I2C::Error: Send,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let thread = thread::spawn(move || {
+            let result = self.run(&mut converter, &flag);
+            (converter, result)
+        });
+        Player { stop, thread }
+    }
+}
+
+/// Converter given back when a playback stops, with the playback result
+pub type Stopped<I2C> = (PCF8591<I2C>, Result<(), <I2C as ErrorType>::Error>);
+
+/// A waveform played in its own thread
+pub struct Player<I2C: I2c> {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Stopped<I2C>>,
+}
+
+impl<I2C: I2c> Player<I2C> {
+    /// Gets a flag stopping the playback when set, e.g. from another thread
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Returns true until the playback is stopped or fails
+    pub fn is_playing(&self) -> bool {
+        !self.thread.is_finished()
+    }
+
+    /// Stops the playback and gives back the converter, with the playback result
+    ///
+    /// The output keeps the last written value.
+    pub fn stop(self) -> Stopped<I2C> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.thread.join() {
+            Ok(stopped) => stopped,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Checks the async driver against a simulated converter
+
+mod common;
+
+use common::block_on;
+use pcf8591::asynch::PCF8591;
+use pcf8591::mock::{MockPCF8591, Transaction};
+use pcf8591::selftest::SelfTest;
+use pcf8591::{Address, DiffPin, Pin};
+
+#[test]
+fn read_and_write() {
+    let mock = MockPCF8591::new(0x48, 2.56);
+    mock.set_input(Pin::AIN1, 1.);
+    mock.set_input(Pin::AIN3, 1.5);
+    let mut converter = PCF8591::from_i2c(mock.clone(), Address::default(), 2.56);
+
+    block_on(async {
+        assert_eq!(converter.analog_read_byte(Pin::AIN1).await.unwrap(), 100);
+        assert_eq!(converter.analog_read_byte(Pin::AIN1).await.unwrap(), 100);
+        assert_eq!(converter.analog_read_differential_byte(DiffPin::AIN1_AIN3).await.unwrap(), -50);
+        assert_eq!(converter.read_all().await.unwrap(), [0, 100, 0, 150]);
+        converter.analog_write(1.5).await.unwrap();
+        converter.disable_output().await.unwrap();
+    });
+
+    assert_eq!(mock.dac(), 150);
+    assert_eq!(mock.output_voltage(), None);
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x41]),
+            Transaction::Read(vec![0x80]),
+            Transaction::Read(vec![100]),
+            Transaction::Read(vec![100]),
+            Transaction::Write(vec![0x51]),
+            Transaction::Read(vec![100]),
+            Transaction::Read(vec![0xCE]),
+            Transaction::Write(vec![0x44]),
+            Transaction::Read(vec![0xCE, 0, 100, 0, 150]),
+            Transaction::Write(vec![0x40, 150]),
+            Transaction::Write(vec![0x00]),
+        ]
+    );
+}
+
+#[test]
+fn failed_output_switch_keeps_state() {
+    let mock = MockPCF8591::new(0x49, 3.3);
+    let mut converter = PCF8591::from_i2c(mock, Address::default(), 3.3);
+    assert!(block_on(converter.disable_output()).is_err());
+    assert!(converter.is_output_enabled());
+}
+
+#[test]
+fn streamed_transfers() {
+    let mock = MockPCF8591::new(0x48, 2.56);
+    mock.set_input(Pin::AIN0, 1.);
+    let mut converter = PCF8591::from_i2c(mock.clone(), Address::default(), 2.56);
+
+    block_on(async {
+        let mut buf = [0; 3];
+        converter.read_burst(Pin::AIN0, &mut buf).await.unwrap();
+        assert_eq!(buf, [100; 3]);
+        converter.write_samples(&[1, 2, 3]).await.unwrap();
+        converter.analog_write_samples(&[0.5, 1.]).await.unwrap();
+    });
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x40]),
+            Transaction::Read(vec![0x80, 100, 100, 100]),
+            Transaction::Write(vec![0x40, 1, 2, 3]),
+            Transaction::Write(vec![0x40, 50, 100]),
+        ]
+    );
+}
+
+#[test]
+fn self_test() {
+    let mock = MockPCF8591::new(0x48, 2.56);
+    mock.connect_output(Pin::AIN3);
+    let mut converter = PCF8591::from_i2c(mock.clone(), Address::default(), 2.56);
+    let report = block_on(converter.self_test(&SelfTest::new(Pin::AIN3))).unwrap();
+    assert_eq!(report.missing_count(), 0);
+    assert!(report.inl < 1e-9);
+    // the DAC gets back its power-on code
+    assert_eq!(mock.dac(), 0);
+}
@@ -0,0 +1,108 @@
+//! Checks several converters sharing a simulated bus
+
+use pcf8591::bus::{Bus, BusPin};
+use pcf8591::mock::{MockBus, MockPCF8591, Transaction};
+use pcf8591::{Address, Calibration, Error, Pin};
+
+fn setup() -> (MockPCF8591, MockPCF8591, Bus<MockBus>) {
+    let first = MockPCF8591::new(0x48, 2.56);
+    let fourth = MockPCF8591::new(0x4B, 2.56);
+    let mut mock = MockBus::new();
+    mock.attach(first.clone());
+    mock.attach(fourth.clone());
+    let mut bus = Bus::new(mock);
+    bus.add(Address::default(), 2.56);
+    bus.add(Address::from_pins(true, true, false), 2.56);
+    (first, fourth, bus)
+}
+
+#[test]
+fn bus_pin_index() {
+    assert_eq!(BusPin::new(3, Pin::AIN2).index(), 14);
+    assert_eq!(BusPin::from_index(14), Some(BusPin::new(3, Pin::AIN2)));
+    assert_eq!(BusPin::from_index(31), Some(BusPin::new(7, Pin::AIN3)));
+    assert_eq!(BusPin::from_index(32), None);
+}
+
+#[test]
+fn read_addressed_pins() {
+    let (first, fourth, mut bus) = setup();
+    first.set_input(Pin::AIN2, 0.5);
+    fourth.set_input(Pin::AIN2, 1.);
+    assert_eq!(bus.devices().collect::<Vec<_>>(), vec![0, 3]);
+    assert_eq!(bus.analog_read_byte(BusPin::new(0, Pin::AIN2)).unwrap(), 50);
+    assert_eq!(bus.analog_read(BusPin::new(3, Pin::AIN2)).unwrap(), 1.);
+
+    // each device keeps its own control byte cache
+    first.clear_transactions();
+    fourth.clear_transactions();
+    bus.analog_read_byte(BusPin::new(0, Pin::AIN2)).unwrap();
+    bus.analog_read_byte(BusPin::new(3, Pin::AIN2)).unwrap();
+    assert_eq!(first.transactions(), vec![Transaction::Read(vec![50])]);
+    assert_eq!(fourth.transactions(), vec![Transaction::Read(vec![100])]);
+}
+
+#[test]
+fn device_api() {
+    let (first, fourth, mut bus) = setup();
+    bus.device(3).unwrap().analog_write_byte(42).unwrap();
+    assert_eq!(fourth.dac(), 42);
+    assert_eq!(first.dac(), 0);
+    assert!(bus.device(1).is_none());
+}
+
+#[test]
+fn read_all_devices() {
+    let (first, fourth, mut bus) = setup();
+    first.set_input(Pin::AIN1, 0.1);
+    fourth.set_input(Pin::AIN3, 0.2);
+    let all = bus.read_all().unwrap();
+    assert_eq!(&all[..4], &[Some(0), Some(10), Some(0), Some(0)]);
+    assert_eq!(&all[4..12], &[None; 8]);
+    assert_eq!(&all[12..16], &[Some(0), Some(0), Some(0), Some(20)]);
+    assert_eq!(&all[16..], &[None; 16]);
+}
+
+#[test]
+fn missing_device() {
+    let (_, _, mut bus) = setup();
+    match bus.analog_read(BusPin::new(5, Pin::AIN0)) {
+        Err(Error::DeviceNotFound(5)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+    assert!(bus.remove(3));
+    assert!(!bus.remove(3));
+    assert_eq!(bus.devices().collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn discover_devices() {
+    let (first, _, mut bus) = setup();
+    bus.device(0).unwrap().analog_write_byte(10).unwrap();
+    let mut mock = bus.release();
+    first.clear_transactions();
+    assert!(pcf8591::bus::detect(&mut mock, Address::default()));
+    assert!(!pcf8591::bus::detect(&mut mock, Address::from_device(1).unwrap()));
+    // probing only reads, the analog output keeps being driven
+    assert_eq!(first.transactions(), vec![Transaction::Read(vec![0x80])]);
+    assert_eq!(first.output_voltage(), Some(0.1));
+
+    let mut bus = Bus::new(mock);
+    assert_eq!(bus.discover(2.56), 2);
+    assert_eq!(bus.devices().collect::<Vec<_>>(), vec![0, 3]);
+    assert_eq!(first.dac(), 10);
+}
+
+#[test]
+fn discover_keeps_added_devices() {
+    let (first, _, mut bus) = setup();
+    bus.device(0).unwrap().set_calibration(Pin::AIN0, Calibration::divider(2.));
+    first.set_input(Pin::AIN0, 0.5);
+    assert_eq!(bus.analog_read(BusPin::new(0, Pin::AIN0)).unwrap(), 1.);
+
+    assert_eq!(bus.discover(3.3), 2);
+    first.clear_transactions();
+    assert_eq!(bus.analog_read(BusPin::new(0, Pin::AIN0)).unwrap(), 1.);
+    // the control byte is sent again after probing
+    assert_eq!(first.transactions()[0], Transaction::Write(vec![0x40]));
+}
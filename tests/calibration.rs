@@ -0,0 +1,78 @@
+//! Checks the calibration of inputs and output against a simulated converter
+
+mod common;
+
+use common::{assert_close, setup};
+use pcf8591::{Calibration, DiffPin, Error, Pin};
+
+#[test]
+fn divider_and_gain() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN0, 1.);
+    mock.set_input(Pin::AIN1, 1.);
+    mock.set_input(Pin::AIN3, 0.5);
+    converter.set_calibration(Pin::AIN0, Calibration::divider(4.));
+    converter.set_calibration(Pin::AIN1, Calibration { offset: 0.1, gain: 2., divider: 1. });
+
+    assert_close(converter.analog_read(Pin::AIN0).unwrap(), 4.);
+    assert_close(converter.analog_read(Pin::AIN1).unwrap(), 2.1);
+    assert_close(converter.analog_read(Pin::AIN2).unwrap(), 0.);
+    // differential readings are not calibrated
+    assert_close(converter.analog_read_differential(DiffPin::AIN0_AIN3).unwrap(), 0.5);
+
+    let all = converter.analog_read_all().unwrap();
+    assert_close(all[0], 4.);
+    assert_close(all[1], 2.1);
+    assert_close(all[3], 0.5);
+}
+
+#[test]
+fn two_point_input() {
+    let (mock, mut converter) = setup();
+    // a buffer with a gain of 0.5 and an offset of 0.2V in front of AIN2
+    let buffer = |v: f64| 0.5 * v + 0.2;
+
+    // each reading returns the conversion started by the previous one
+    mock.set_input(Pin::AIN2, buffer(0.5));
+    converter.analog_read_byte(Pin::AIN2).unwrap();
+    let low = converter.analog_read_byte(Pin::AIN2).unwrap();
+    mock.set_input(Pin::AIN2, buffer(3.5));
+    converter.analog_read_byte(Pin::AIN2).unwrap();
+    let high = converter.analog_read_byte(Pin::AIN2).unwrap();
+    let (low, high) = (converter.code_to_voltage(low), converter.code_to_voltage(high));
+
+    let calibration = Calibration::default().two_point((low, 0.5), (high, 3.5)).unwrap();
+    assert_close(calibration.gain, 2.);
+    assert_close(calibration.offset, -0.4);
+    converter.set_calibration(Pin::AIN2, calibration);
+
+    mock.set_input(Pin::AIN2, buffer(2.));
+    converter.analog_read(Pin::AIN2).unwrap();
+    assert_close(converter.analog_read(Pin::AIN2).unwrap(), 2.);
+}
+
+#[test]
+fn two_point_keeps_divider() {
+    let calibration = Calibration::divider(2.).two_point((0.5, 1.), (1.5, 3.)).unwrap();
+    assert_close(calibration.divider, 2.);
+    assert_close(calibration.apply(1.), 2.);
+    assert_close(calibration.invert(2.), 1.);
+    assert!(Calibration::default().two_point((1., 0.), (1., 2.)).is_none());
+}
+
+#[test]
+fn output() {
+    let (mock, mut converter) = setup();
+    // an amplifier with a gain of 2 after AOUT
+    converter.set_output_calibration(Calibration { offset: 0., gain: 2., divider: 1. });
+    converter.analog_write(2.).unwrap();
+    assert_eq!(mock.dac(), 100);
+    assert_close(mock.output_voltage().unwrap(), 1.);
+
+    match converter.analog_write(5.2) {
+        Err(Error::VoltageOutOfRange(v)) => assert_close(v, 5.2),
+        r => panic!("unexpected {:?}", r),
+    }
+    converter.analog_write_saturating(5.2).unwrap();
+    assert_eq!(mock.dac(), 255);
+}
@@ -0,0 +1,36 @@
+//! Fixtures shared by the integration tests
+
+#![allow(dead_code)]
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use pcf8591::mock::MockPCF8591;
+use pcf8591::{Address, PCF8591};
+
+/// Simulated converter at the default address, with a 2.56V reference (10mV per code)
+pub fn setup() -> (MockPCF8591, PCF8591<MockPCF8591>) {
+    let mock = MockPCF8591::new(0x48, 2.56);
+    let converter = PCF8591::from_i2c(mock.clone(), Address::default(), 2.56);
+    (mock, converter)
+}
+
+pub fn assert_close(a: f64, b: f64) {
+    assert_within(a, b, 1e-9);
+}
+
+pub fn assert_within(a: f64, b: f64, epsilon: f64) {
+    assert!((a - b).abs() < epsilon, "{} != {}", a, b);
+}
+
+/// Polls a future to completion, the simulated bus never being pending
+pub fn block_on<F: Future>(f: F) -> F::Output {
+    let mut f = pin!(f);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(out) = f.as_mut().poll(&mut cx) {
+            return out;
+        }
+    }
+}
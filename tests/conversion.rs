@@ -0,0 +1,87 @@
+//! Checks the voltage conversions against a simulated converter
+
+mod common;
+
+use common::assert_close;
+use pcf8591::mock::MockPCF8591;
+use pcf8591::{Address, DiffPin, Error, Pin, PCF8591};
+
+fn setup(v_ref: f64, v_agnd: f64) -> (MockPCF8591, PCF8591<MockPCF8591>) {
+    let mock = MockPCF8591::new(0x4A, v_ref);
+    mock.set_v_agnd(v_agnd);
+    let mut converter = PCF8591::from_i2c(mock.clone(), Address::from_pins(false, true, false), v_ref);
+    converter.set_v_agnd(v_agnd);
+    (mock, converter)
+}
+
+#[test]
+fn read_voltage() {
+    let (mock, mut converter) = setup(2.56, 0.);
+    mock.set_input(Pin::AIN2, 1.234);
+    assert_close(converter.analog_read(Pin::AIN2).unwrap(), 1.23);
+}
+
+#[test]
+fn read_voltage_with_agnd() {
+    let (mock, mut converter) = setup(3.56, 1.);
+    mock.set_input(Pin::AIN0, 2.5);
+    mock.set_input(Pin::AIN1, 1.);
+    mock.set_input(Pin::AIN2, 0.5);
+    mock.set_input(Pin::AIN3, 1.8);
+    assert_close(converter.analog_read(Pin::AIN0).unwrap(), 2.5);
+    let v = converter.analog_read_all().unwrap();
+    for (v, e) in v.iter().zip([2.5, 1., 1., 1.8].iter()) {
+        assert_close(*v, *e);
+    }
+}
+
+#[test]
+fn read_differential_voltage() {
+    let (mock, mut converter) = setup(3.56, 1.);
+    mock.set_input(Pin::AIN0, 1.2);
+    mock.set_input(Pin::AIN3, 1.5);
+    assert_close(converter.analog_read_differential(DiffPin::AIN0_AIN3).unwrap(), -0.3);
+}
+
+#[test]
+fn write_voltage_rounds_to_nearest() {
+    let (mock, mut converter) = setup(2.56, 0.);
+    converter.analog_write(1.004).unwrap();
+    assert_eq!(mock.dac(), 100);
+    converter.analog_write(1.006).unwrap();
+    assert_eq!(mock.dac(), 101);
+    converter.analog_write(2.56).unwrap();
+    assert_eq!(mock.dac(), 255);
+}
+
+#[test]
+fn write_voltage_with_agnd() {
+    let (mock, mut converter) = setup(3.56, 1.);
+    converter.analog_write(1.5).unwrap();
+    assert_eq!(mock.dac(), 50);
+    assert_close(mock.output_voltage().unwrap(), 1.5);
+}
+
+#[test]
+fn write_voltage_out_of_range() {
+    let (mock, mut converter) = setup(3.56, 1.);
+    converter.analog_write(2.).unwrap();
+    for &v in &[0.5, 3.6, f64::NAN, f64::INFINITY] {
+        match converter.analog_write(v) {
+            Err(Error::VoltageOutOfRange(_)) => (),
+            r => panic!("{} should be rejected, got {:?}", v, r),
+        }
+    }
+    assert_eq!(mock.dac(), 100);
+}
+
+#[test]
+fn write_voltage_saturating() {
+    let (mock, mut converter) = setup(3.56, 1.);
+    converter.analog_write_saturating(0.5).unwrap();
+    assert_eq!(mock.dac(), 0);
+    converter.analog_write_saturating(10.).unwrap();
+    assert_eq!(mock.dac(), 255);
+    assert!(converter.analog_write_saturating(f64::NAN).is_err());
+    assert_eq!(mock.dac(), 255);
+}
@@ -0,0 +1,87 @@
+//! Checks oversampled readings against a simulated converter
+
+mod common;
+
+use common::{assert_close, setup};
+use pcf8591::mock::Transaction;
+use pcf8591::{Calibration, Oversampling, Pin};
+
+#[test]
+fn samples() {
+    assert_eq!(Oversampling::None.samples(), 1);
+    assert_eq!(Oversampling::Average(0).samples(), 1);
+    assert_eq!(Oversampling::Average(10).samples(), 10);
+    assert_eq!(Oversampling::Decimate(3).samples(), 64);
+    assert_eq!(Oversampling::Decimate(12).samples(), 65536);
+    assert_eq!(Oversampling::Average(4).code(402), 100.5);
+    assert_eq!(Oversampling::Decimate(1).code(402), 100.5);
+    assert_eq!(Oversampling::Decimate(1).code(403), 100.5);
+}
+
+#[test]
+fn burst_reads_without_auto_increment() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN2, 1.);
+    assert_eq!(converter.read_oversampled(Pin::AIN2, Oversampling::Average(40)).unwrap(), 100.);
+    let mut first = vec![0x80];
+    first.extend_from_slice(&[100; 32]);
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x42]),
+            Transaction::Read(first),
+            Transaction::Read(vec![100; 9]),
+        ]
+    );
+
+    // the control byte is only sent when switching input
+    mock.clear_transactions();
+    converter.read_oversampled(Pin::AIN2, Oversampling::Average(2)).unwrap();
+    assert_eq!(mock.transactions().len(), 1);
+}
+
+#[test]
+fn decimation() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN0, 1.);
+    assert_eq!(converter.read_decimated(Pin::AIN0, 2).unwrap(), 400);
+    mock.set_input(Pin::AIN0, 2.55);
+    assert_eq!(converter.read_decimated(Pin::AIN0, 8).unwrap(), 0xFF00);
+}
+
+#[test]
+fn analog_read_applies_oversampling() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN1, 1.);
+    converter.set_oversampling(Pin::AIN1, Oversampling::Decimate(2));
+    converter.set_calibration(Pin::AIN1, Calibration::divider(2.));
+    assert_eq!(converter.oversampling(Pin::AIN1), Oversampling::Decimate(2));
+    assert_eq!(converter.oversampling(Pin::AIN0), Oversampling::None);
+
+    assert_close(converter.analog_read(Pin::AIN1).unwrap(), 2.);
+    let mut burst = vec![0x80];
+    burst.extend_from_slice(&[100; 16]);
+    assert_eq!(mock.transactions()[1], Transaction::Read(burst));
+}
+
+#[test]
+fn pin_readings_apply_oversampling() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN3, 1.);
+    converter.set_oversampling(Pin::AIN3, Oversampling::Average(4));
+    let mut burst = vec![0x80];
+    burst.extend_from_slice(&[100; 4]);
+
+    assert_close(converter.read_sensor(Pin::AIN3, &|v: f64| v * 10.).unwrap(), 10.);
+    assert_eq!(mock.transactions()[1], Transaction::Read(burst));
+
+    mock.clear_transactions();
+    assert_close(converter.analog_read_channel(Pin::AIN3.into()).unwrap(), 1.);
+    assert_eq!(mock.transactions(), vec![Transaction::Read(vec![100; 5])]);
+
+    mock.clear_transactions();
+    let sample = converter.sample(Pin::AIN3.into()).unwrap();
+    assert_eq!(sample.code, 100);
+    assert_close(sample.voltage, 1.);
+    assert_eq!(mock.transactions(), vec![Transaction::Read(vec![100; 5])]);
+}
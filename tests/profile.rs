@@ -0,0 +1,96 @@
+//! Checks storing and loading converter profiles
+
+mod common;
+
+use common::assert_close;
+use pcf8591::mock::MockPCF8591;
+use pcf8591::profile::{Profile, ProfileError};
+use pcf8591::{Address, Calibration, InputMode, Pin, PCF8591};
+
+fn calibrated() -> Profile {
+    let mut profile = Profile::new(3.3);
+    profile.address = Address::from_pins(true, false, false);
+    profile.v_agnd = 0.1;
+    profile.input_mode = InputMode::Mixed;
+    profile.calibrations[1] = Calibration::divider(2.);
+    profile.output_calibration = Calibration { offset: 0.05, gain: 1.5, divider: 1. };
+    profile
+}
+
+#[test]
+fn toml_round_trip() {
+    let profile = calibrated();
+    let toml = profile.to_toml().unwrap();
+    assert_eq!(Profile::from_toml(&toml).unwrap(), profile);
+}
+
+#[test]
+fn json_round_trip() {
+    let profile = calibrated();
+    let json = profile.to_json().unwrap();
+    assert_eq!(Profile::from_json(&json).unwrap(), profile);
+}
+
+#[test]
+fn files() {
+    let dir = std::env::temp_dir();
+    let toml = dir.join(format!("pcf8591-{}.toml", std::process::id()));
+    let json = dir.join(format!("pcf8591-{}.json", std::process::id()));
+    let profile = calibrated();
+    profile.save_toml(&toml).unwrap();
+    profile.save_json(&json).unwrap();
+    assert_eq!(Profile::load_toml(&toml).unwrap(), profile);
+    assert_eq!(Profile::load_json(&json).unwrap(), profile);
+    std::fs::remove_file(toml).unwrap();
+    std::fs::remove_file(json).unwrap();
+
+    match Profile::load_toml(dir.join("pcf8591-missing.toml")) {
+        Err(ProfileError::Io(_)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+}
+
+#[test]
+fn defaults_and_validation() {
+    let profile = Profile::from_toml("v_ref = 2.56\n[[calibrations]]\ndivider = 3.0").unwrap_err();
+    // all four calibrations are required once the array is given
+    assert!(matches!(profile, ProfileError::TomlDe(_)));
+    let toml = "v_ref = 2.56\n[[calibrations]]\ndivider = 3.0\n[[calibrations]]\n[[calibrations]]\n[[calibrations]]";
+    let profile = Profile::from_toml(toml).unwrap();
+    assert_eq!(profile.calibrations[0], Calibration::divider(3.));
+    assert_eq!(profile.calibrations[3], Calibration::default());
+
+    let profile = Profile::from_toml("v_ref = 2.56").unwrap();
+    assert_eq!(profile, Profile::new(2.56));
+
+    let profile = Profile::from_json(r#"{"v_ref": 2.56, "output_calibration": {"gain": 2.0}}"#).unwrap();
+    assert_eq!(profile.output_calibration, Calibration { offset: 0., gain: 2., divider: 1. });
+
+    match Profile::from_json(r#"{"address": 32, "v_ref": 3.3}"#) {
+        Err(ProfileError::Json(e)) => assert!(e.to_string().contains("invalid PCF8591 address")),
+        r => panic!("unexpected {:?}", r),
+    }
+}
+
+#[test]
+fn converter_from_profile() {
+    let mock = MockPCF8591::new(0x49, 3.3);
+    mock.set_v_agnd(0.1);
+    mock.set_input(Pin::AIN1, 1.7);
+    let profile = calibrated();
+    let mut converter = PCF8591::from_profile(mock.clone(), &profile);
+
+    assert_eq!(converter.v_agnd(), 0.1);
+    converter.analog_read(Pin::AIN1).unwrap();
+    assert_close(converter.analog_read(Pin::AIN1).unwrap(), 3.4);
+
+    let saved = converter.profile();
+    assert_eq!(saved.address, profile.address);
+    assert_eq!(saved.calibrations, profile.calibrations);
+    assert_eq!(saved.output_calibration, profile.output_calibration);
+    assert_eq!(saved.input_mode, InputMode::Mixed);
+    assert_eq!(saved, profile);
+
+    converter.set_input_mode(InputMode::TwoDifferential);
+    assert_eq!(converter.profile().input_mode, InputMode::TwoDifferential);
+}
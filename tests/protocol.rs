@@ -0,0 +1,248 @@
+//! Checks the exact I2C transactions sent to the converter
+
+mod common;
+
+use common::setup;
+use pcf8591::mock::{MockPCF8591, Transaction};
+use pcf8591::{Address, DiffPin, Error, Pin, PCF8591};
+
+#[test]
+fn read_byte_sends_control_and_discards_stale_byte() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN1, 1.);
+    assert_eq!(converter.analog_read_byte(Pin::AIN1).unwrap(), 100);
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x41]),
+            Transaction::Read(vec![0x80]),
+            Transaction::Read(vec![100]),
+        ]
+    );
+}
+
+#[test]
+fn read_byte_caches_pin() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN0, 0.5);
+    converter.analog_read_byte(Pin::AIN0).unwrap();
+    mock.clear_transactions();
+
+    mock.set_input(Pin::AIN0, 0.6);
+    // the conversion returned was started by the previous read
+    assert_eq!(converter.analog_read_byte(Pin::AIN0).unwrap(), 50);
+    assert_eq!(converter.analog_read_byte(Pin::AIN0).unwrap(), 60);
+    assert_eq!(
+        mock.transactions(),
+        vec![Transaction::Read(vec![50]), Transaction::Read(vec![60])]
+    );
+}
+
+#[test]
+fn read_byte_changing_pin_resends_control() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN0, 0.5);
+    mock.set_input(Pin::AIN3, 2.);
+    converter.analog_read_byte(Pin::AIN0).unwrap();
+    mock.clear_transactions();
+
+    assert_eq!(converter.analog_read_byte(Pin::AIN3).unwrap(), 200);
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x43]),
+            Transaction::Read(vec![50]),
+            Transaction::Read(vec![200]),
+        ]
+    );
+}
+
+#[test]
+fn read_differential_byte() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN0, 0.5);
+    mock.set_input(Pin::AIN1, 1.);
+    mock.set_input(Pin::AIN3, 0.8);
+    assert_eq!(converter.analog_read_differential_byte(DiffPin::AIN1_AIN3).unwrap(), 20);
+    assert_eq!(converter.analog_read_differential_byte(DiffPin::AIN0_AIN1).unwrap(), -50);
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x51]),
+            Transaction::Read(vec![0x80]),
+            Transaction::Read(vec![20]),
+            Transaction::Write(vec![0x70]),
+            Transaction::Read(vec![20]),
+            Transaction::Read(vec![0xCE]),
+        ]
+    );
+}
+
+#[test]
+fn read_all_uses_auto_increment() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN0, 0.1);
+    mock.set_input(Pin::AIN1, 0.2);
+    mock.set_input(Pin::AIN2, 0.3);
+    mock.set_input(Pin::AIN3, 0.4);
+    assert_eq!(converter.read_all().unwrap(), [10, 20, 30, 40]);
+    assert_eq!(converter.read_all().unwrap(), [10, 20, 30, 40]);
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x44]),
+            Transaction::Read(vec![0x80, 10, 20, 30, 40]),
+            Transaction::Write(vec![0x44]),
+            Transaction::Read(vec![10, 10, 20, 30, 40]),
+        ]
+    );
+
+    // single reads need the control byte again
+    mock.clear_transactions();
+    converter.analog_read_byte(Pin::AIN0).unwrap();
+    assert_eq!(mock.transactions()[0], Transaction::Write(vec![0x40]));
+}
+
+#[test]
+fn write_byte_invalidates_pin() {
+    let (mock, mut converter) = setup();
+    converter.analog_read_byte(Pin::AIN2).unwrap();
+    converter.analog_write_byte(0xAB).unwrap();
+    converter.analog_read_byte(Pin::AIN2).unwrap();
+    assert_eq!(mock.dac(), 0xAB);
+    assert_eq!(
+        mock.transactions()[3..],
+        [
+            Transaction::Write(vec![0x40, 0xAB]),
+            Transaction::Write(vec![0x42]),
+            Transaction::Read(vec![0]),
+            Transaction::Read(vec![0]),
+        ]
+    );
+}
+
+#[test]
+fn output_state_is_kept_by_reads_and_writes() {
+    let (mock, mut converter) = setup();
+    converter.analog_write_byte(100).unwrap();
+    assert_eq!(mock.output_voltage(), Some(1.));
+
+    converter.disable_output().unwrap();
+    assert!(!converter.is_output_enabled());
+    assert_eq!(mock.output_voltage(), None);
+
+    converter.analog_read_byte(Pin::AIN1).unwrap();
+    converter.read_all().unwrap();
+    converter.analog_write_byte(200).unwrap();
+    assert_eq!(mock.output_voltage(), None);
+
+    converter.enable_output().unwrap();
+    assert_eq!(mock.output_voltage(), Some(2.));
+    assert_eq!(
+        mock.transactions()
+            .into_iter()
+            .filter_map(|t| match t {
+                Transaction::Write(bytes) => Some(bytes),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        vec![
+            vec![0x40, 100],
+            vec![0x00],
+            vec![0x01],
+            vec![0x04],
+            vec![0x00, 200],
+            vec![0x40],
+        ]
+    );
+}
+
+#[test]
+fn addresses() {
+    assert_eq!(Address::default().value(), 0x48);
+    assert_eq!(Address::from_pins(true, false, false).value(), 0x49);
+    assert_eq!(Address::from_pins(false, true, true).value(), 0x4E);
+    assert_eq!(Address::from_pins(true, true, true).value(), 0x4F);
+    assert_eq!(Address::from_device(5), Some(Address::from_pins(true, false, true)));
+    assert_eq!(Address::from_device(8), None);
+    assert_eq!(Address::new(0x4B).map(|a| a.device()), Some(3));
+    assert_eq!(Address::new(0x20), None);
+    assert_eq!(Address::new(0x148), None);
+}
+
+#[test]
+fn bus_error() {
+    let mock = MockPCF8591::new(0x49, 3.3);
+    let mut converter = PCF8591::from_i2c(mock, Address::default(), 3.3);
+    match converter.analog_read_byte(Pin::AIN0) {
+        Err(Error::I2c(_)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+}
+
+#[test]
+fn failed_output_switch_keeps_state() {
+    let mock = MockPCF8591::new(0x49, 3.3);
+    let mut converter = PCF8591::from_i2c(mock, Address::default(), 3.3);
+    assert!(converter.disable_output().is_err());
+    assert!(converter.is_output_enabled());
+}
+
+#[test]
+fn streamed_writes() {
+    let (mock, mut converter) = setup();
+    converter.write_samples(&[1, 2, 3, 4, 5]).unwrap();
+    converter.write_samples(&[]).unwrap();
+    converter.write_samples_chunked(&[6, 7, 8], 2).unwrap();
+    assert_eq!(mock.dac(), 8);
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x40, 1, 2, 3, 4, 5]),
+            Transaction::Write(vec![0x40, 6, 7]),
+            Transaction::Write(vec![0x40, 8]),
+        ]
+    );
+}
+
+#[test]
+fn streamed_voltage_writes() {
+    let (mock, mut converter) = setup();
+    let voltages = (0..100).map(|i| i as f64 * 0.02).collect::<Vec<_>>();
+    converter.analog_write_samples(&voltages).unwrap();
+    let transactions = mock.transactions();
+    assert_eq!(transactions.len(), 2);
+    match transactions[1] {
+        Transaction::Write(ref bytes) => {
+            assert_eq!(bytes.len(), 37);
+            assert_eq!(bytes[0], 0x40);
+            assert_eq!(bytes[36], 198);
+        }
+        ref t => panic!("unexpected {:?}", t),
+    }
+
+    mock.clear_transactions();
+    match converter.analog_write_samples(&[1., 2., 3.]) {
+        Err(Error::VoltageOutOfRange(v)) => assert_eq!(v, 3.),
+        r => panic!("unexpected {:?}", r),
+    }
+    assert!(mock.transactions().is_empty());
+}
+
+#[test]
+fn burst_read_discards_stale_byte() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN3, 1.);
+    let mut buf = [0; 4];
+    converter.read_burst(Pin::AIN3, &mut buf).unwrap();
+    assert_eq!(buf, [100; 4]);
+    converter.read_burst(Pin::AIN3, &mut buf[..2]).unwrap();
+    assert_eq!(
+        mock.transactions(),
+        vec![
+            Transaction::Write(vec![0x43]),
+            Transaction::Read(vec![0x80, 100, 100, 100, 100]),
+            Transaction::Read(vec![100, 100, 100]),
+        ]
+    );
+}
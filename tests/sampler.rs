@@ -0,0 +1,105 @@
+//! Checks fixed-rate sampling against a simulated converter
+
+mod common;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::block_on;
+use futures_util::StreamExt;
+use pcf8591::mock::MockPCF8591;
+use pcf8591::{asynch, Address, Channel, DiffPin, Pin, PCF8591};
+
+fn mock() -> MockPCF8591 {
+    let mock = MockPCF8591::new(0x48, 2.56);
+    mock.set_input(Pin::AIN0, 1.);
+    mock.set_input(Pin::AIN3, 1.5);
+    mock
+}
+
+const CHANNELS: [Channel; 2] = [
+    Channel::Single(Pin::AIN0),
+    Channel::Differential(DiffPin::AIN0_AIN3),
+];
+
+#[test]
+fn sampler_reads_channels_at_rate() {
+    let mut converter = PCF8591::from_i2c(mock(), Address::default(), 2.56);
+    let start = Instant::now();
+    let scans = converter
+        .sampler(&CHANNELS, 200.)
+        .take(5)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let elapsed = start.elapsed();
+
+    assert!(elapsed >= Duration::from_millis(20), "{:?}", elapsed);
+    assert_eq!(scans.len(), 5);
+    // first readings return the conversion of the previous read, as per the chip protocol
+    for scan in &scans {
+        let voltages = scan.samples.iter().map(|s| s.voltage).collect::<Vec<_>>();
+        assert_eq!(voltages, vec![1., -0.5]);
+        assert_eq!(scan.samples[0].channel, Channel::Single(Pin::AIN0));
+        assert_eq!(scan.samples[1].code, 0xCE);
+    }
+    // scans follow 5ms deadlines, the timestamps being those of the conversions;
+    // a late scan is not delayed further so only the overall span is bounded
+    for w in scans.windows(2) {
+        assert!(w[1].samples[0].timestamp > w[0].samples[0].timestamp);
+    }
+    let span = scans[4].samples[0].timestamp - scans[0].samples[0].timestamp;
+    assert!(span >= Duration::from_millis(15), "{:?}", span);
+}
+
+#[test]
+fn sampler_reports_overruns() {
+    let mut converter = PCF8591::from_i2c(mock(), Address::default(), 2.56);
+    let mut sampler = converter.sampler(&[Pin::AIN0.into()], 100.);
+    assert_eq!(sampler.next().unwrap().unwrap().overruns, 0);
+    thread::sleep(Duration::from_millis(35));
+    // the late scan reports the deadlines it skipped
+    assert!(sampler.next().unwrap().unwrap().overruns >= 2);
+    assert_eq!(sampler.next().unwrap().unwrap().overruns, 0);
+}
+
+struct Delay;
+
+impl embedded_hal_async::delay::DelayNs for Delay {
+    async fn delay_ns(&mut self, ns: u32) {
+        thread::sleep(Duration::from_nanos(ns as u64));
+    }
+}
+
+#[test]
+fn stream_reads_channels_at_rate() {
+    let mut converter = asynch::PCF8591::from_i2c(mock(), Address::default(), 2.56);
+    let start = Instant::now();
+    let scans = block_on(converter.stream(&CHANNELS, 200., Delay).take(4).collect::<Vec<_>>());
+    assert!(start.elapsed() >= Duration::from_millis(15));
+    assert_eq!(scans.len(), 4);
+    let samples = &scans[3].as_ref().unwrap().samples;
+    assert_eq!(samples[0].voltage, 1.);
+    assert_eq!(samples[1].voltage, -0.5);
+}
+
+#[test]
+fn sample_timestamp_accounts_for_conversion_lag() {
+    let mut converter = PCF8591::from_i2c(mock(), Address::default(), 2.56);
+    let before = Instant::now();
+    let first = converter.sample(Pin::AIN0.into()).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    let second = converter.sample(Pin::AIN0.into()).unwrap();
+
+    let third = converter.sample(Pin::AIN0.into()).unwrap();
+
+    // the second read returns the conversion started by the first one, before sleeping
+    assert!(first.timestamp >= before);
+    assert!(second.timestamp - first.timestamp < Duration::from_millis(20));
+    assert!(third.timestamp - first.timestamp >= Duration::from_millis(20));
+
+    let all = converter.sample_all().unwrap();
+    assert_eq!(all[0].voltage, 1.);
+    assert_eq!(all[3].voltage, 1.5);
+    assert!(all.iter().all(|s| s.timestamp == all[0].timestamp));
+    assert!(all[0].timestamp > third.timestamp);
+}
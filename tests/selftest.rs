@@ -0,0 +1,79 @@
+//! Checks DAC sweeps read back on a simulated converter
+
+mod common;
+
+use common::setup;
+use pcf8591::mock::Transaction;
+use pcf8591::selftest::{SelfTest, Tolerances};
+use pcf8591::{Error, Pin};
+
+#[test]
+fn loopback_passes() {
+    let (mock, mut converter) = setup();
+    mock.connect_output(Pin::AIN2);
+    let report = converter.self_test(&SelfTest::new(Pin::AIN2)).unwrap();
+    assert!(report.offset.abs() < 1e-9, "{}", report);
+    assert!(report.gain_error.abs() < 1e-9, "{}", report);
+    assert!(report.inl < 1e-9, "{}", report);
+    assert!(report.dnl < 1e-9, "{}", report);
+    assert_eq!(report.missing_count(), 0);
+    assert_eq!(report.missing_codes().next(), None);
+
+    // every code is written then read back in a burst, the last code written back
+    let transactions = mock.transactions();
+    assert_eq!(transactions.len(), 3 * 256 + 1);
+    assert_eq!(transactions[3], Transaction::Write(vec![0x40, 1]));
+    assert_eq!(transactions[4], Transaction::Write(vec![0x42]));
+    assert_eq!(transactions[5], Transaction::Read(vec![0, 1, 1, 1, 1]));
+    assert_eq!(transactions[3 * 256], Transaction::Write(vec![0x40, 0]));
+    assert_eq!(mock.dac(), 0);
+}
+
+#[test]
+fn unwired_input_fails() {
+    let (mock, mut converter) = setup();
+    mock.connect_output(Pin::AIN2);
+    mock.set_input(Pin::AIN0, 1.);
+    match converter.self_test(&SelfTest::new(Pin::AIN0)) {
+        Err(Error::SelfTestFailed(report)) => {
+            assert!((report.gain_error + 1.).abs() < 1e-9, "{}", report);
+            assert!((report.offset - 100.).abs() < 1e-6, "{}", report);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // nothing passes a sweep without unsaturated readings
+    let loose = Tolerances {
+        offset: 1e9,
+        gain_error: 1e9,
+        inl: 1e9,
+        dnl: 1e9,
+        missing_codes: 256,
+    };
+    let test = SelfTest { pin: Pin::AIN3, samples: 1, tolerances: loose };
+    assert!(matches!(converter.self_test(&test), Err(Error::SelfTestFailed(_))));
+}
+
+#[test]
+fn output_state_is_restored() {
+    let (mock, mut converter) = setup();
+    mock.connect_output(Pin::AIN1);
+    converter.disable_output().unwrap();
+    converter.self_test(&SelfTest::new(Pin::AIN1)).unwrap();
+    assert!(!converter.is_output_enabled());
+    assert_eq!(mock.output_voltage(), None);
+}
+
+#[test]
+fn dac_code_is_restored() {
+    let (mock, mut converter) = setup();
+    mock.connect_output(Pin::AIN1);
+    converter.analog_write_byte(42).unwrap();
+    converter.self_test(&SelfTest::new(Pin::AIN1)).unwrap();
+    assert_eq!(mock.dac(), 42);
+    assert_eq!(mock.output_voltage(), Some(0.42));
+
+    converter.write_samples(&[7, 9]).unwrap();
+    converter.self_test(&SelfTest::new(Pin::AIN1)).unwrap();
+    assert_eq!(mock.dac(), 9);
+}
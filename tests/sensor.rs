@@ -0,0 +1,99 @@
+//! Checks the conversion of readings into physical units
+
+mod common;
+
+use common::{assert_close, assert_within, setup};
+use pcf8591::mock::MockPCF8591;
+use pcf8591::sensor::{breakout, Beta, Divider, Input, Lookup, Polynomial, SteinhartHart};
+use pcf8591::{Address, Pin, PCF8591};
+
+#[test]
+fn lookup() {
+    let points = [(0., 10.), (1., 20.), (3., 0.)];
+    let table = Lookup::new(Input::Voltage, &points).unwrap();
+    assert_close(table.interpolate(-1.), 10.);
+    assert_close(table.interpolate(0.5), 15.);
+    assert_close(table.interpolate(1.), 20.);
+    assert_close(table.interpolate(2.5), 5.);
+    assert_close(table.interpolate(4.), 0.);
+
+    assert!(Lookup::new(Input::Code, &[]).is_none());
+    assert!(Lookup::new(Input::Code, &[(1., 0.), (1., 2.)]).is_none());
+}
+
+#[test]
+fn polynomial() {
+    let p = Polynomial::new(Input::Code, &[1., -2., 0.5]);
+    assert_close(p.evaluate(0.), 1.);
+    assert_close(p.evaluate(4.), 1.);
+    assert_close(Polynomial::new(Input::Code, &[]).evaluate(3.), 0.);
+}
+
+#[test]
+fn divider() {
+    let low = Divider::low_side(10_000., 3.3);
+    assert_within(low.resistance(1.65), 10_000., 1e-6);
+    assert_within(low.resistance(1.1), 5_000., 1e-6);
+    let high = Divider::high_side(10_000., 3.3);
+    assert_within(high.resistance(1.1), 20_000., 1e-6);
+}
+
+#[test]
+fn thermistors() {
+    let beta = Beta {
+        r0: 10_000.,
+        t0: 25.,
+        beta: 3950.,
+        divider: Divider::low_side(10_000., 3.3),
+    };
+    assert_close(beta.temperature(10_000.), 25.);
+    // 10k 3950K thermistors are about 33.6kΩ at 0°C
+    assert_within(beta.temperature(33_620.), 0., 0.1);
+
+    // coefficients of a 10k thermistor, -40°C to 125°C
+    let sh = SteinhartHart {
+        a: 1.125_308_852e-3,
+        b: 2.347_737_37e-4,
+        c: 8.566_304_2e-8,
+        divider: beta.divider,
+    };
+    assert_within(sh.temperature(10_000.), 25., 0.1);
+}
+
+#[test]
+fn breakout_module() {
+    let mock = MockPCF8591::new(0x48, 3.3);
+    let mut converter = PCF8591::from_i2c(mock.clone(), Address::default(), 3.3);
+    // thermistor at 10kΩ, i.e. half the supply
+    mock.set_input(breakout::THERMISTOR, 1.65);
+    mock.set_input(breakout::POT, 0.825);
+
+    let thermistor = breakout::thermistor(3.3);
+    converter.read_sensor(breakout::THERMISTOR, &thermistor).unwrap();
+    assert_close(converter.read_sensor(breakout::THERMISTOR, &thermistor).unwrap(), 25.);
+
+    converter.read_sensor(breakout::POT, &breakout::pot(3.3)).unwrap();
+    assert_close(converter.read_sensor(breakout::POT, &breakout::pot(3.3)).unwrap(), 0.25);
+
+    // brighter means a lower resistance and voltage
+    let ldr = breakout::ldr(3.3);
+    mock.set_input(breakout::LDR, 2.);
+    converter.read_sensor(breakout::LDR, &ldr).unwrap();
+    let dark = converter.read_sensor(breakout::LDR, &ldr).unwrap();
+    mock.set_input(breakout::LDR, 0.5);
+    converter.read_sensor(breakout::LDR, &ldr).unwrap();
+    let bright = converter.read_sensor(breakout::LDR, &ldr).unwrap();
+    assert!(bright > dark, "{} <= {}", bright, dark);
+}
+
+#[test]
+fn codes_and_closures() {
+    let (mock, mut converter) = setup();
+    mock.set_input(Pin::AIN2, 1.);
+
+    let points = [(0., 0.), (200., 100.)];
+    let table = Lookup::new(Input::Code, &points).unwrap();
+    converter.read_sensor(Pin::AIN2, &table).unwrap();
+    assert_close(converter.read_sensor(Pin::AIN2, &table).unwrap(), 50.);
+    assert_close(converter.read_sensor(Pin::AIN2, &|v: f64| v * 10.).unwrap(), 10.);
+}
@@ -0,0 +1,84 @@
+//! Checks waveforms played on a simulated converter
+
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use common::{assert_close, setup};
+use pcf8591::mock::Transaction;
+use pcf8591::waveform::{Generator, Shape, Waveform};
+
+#[test]
+fn shapes() {
+    let sine = Waveform::new(Shape::Sine, 10., 1., 2.);
+    assert_close(sine.voltage(0.), 1.);
+    assert_close(sine.voltage(0.025), 1.5);
+    assert_close(sine.voltage(0.05), 2.);
+    assert_close(sine.voltage(0.1), 1.);
+
+    let triangle = Waveform::new(Shape::Triangle, 1., 0., 2.);
+    assert_close(triangle.voltage(0.25), 1.);
+    assert_close(triangle.voltage(0.5), 2.);
+    assert_close(triangle.voltage(0.75), 1.);
+
+    let sawtooth = Waveform::new(Shape::Sawtooth, 2., 0., 1.);
+    assert_close(sawtooth.voltage(0.125), 0.25);
+    assert_close(sawtooth.voltage(0.625), 0.25);
+
+    let square = Waveform::new(Shape::Square(0.25), 1., 0.5, 1.5);
+    assert_close(square.voltage(0.2), 1.5);
+    assert_close(square.voltage(0.3), 0.5);
+
+    let table = Waveform::new(Shape::Table(vec![0., 1., 0.5]), 1., 0., 3.);
+    assert_close(table.voltage(0.1), 0.);
+    assert_close(table.voltage(0.5), 3.);
+    assert_close(table.voltage(0.9), 1.5);
+}
+
+#[test]
+fn multi_byte_writes() {
+    let (mock, mut converter) = setup();
+    // 4 updates per transaction, one transaction per millisecond
+    let sawtooth = Waveform::new(Shape::Sawtooth, 1000., 0., 2.56);
+    let generator = Generator::new(sawtooth, 4000.);
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(20));
+            stop.store(true, Ordering::Relaxed);
+        });
+        generator.run(&mut converter, &stop).unwrap();
+    });
+
+    let transactions = mock.transactions();
+    assert!(transactions.len() >= 10, "{}", transactions.len());
+    assert_eq!(transactions[0], Transaction::Write(vec![0x40, 0, 64, 128, 192]));
+    for t in &transactions {
+        match *t {
+            Transaction::Write(ref bytes) => assert_eq!(bytes.len(), 5),
+            Transaction::Read(_) => panic!("unexpected read"),
+        }
+    }
+}
+
+#[test]
+fn player() {
+    let (mock, converter) = setup();
+    let square = Waveform::new(Shape::Square(0.5), 100., 0., 5.);
+    let player = Generator::new(square, 1000.).chunk(2).spawn(converter);
+    thread::sleep(Duration::from_millis(20));
+    assert!(player.is_playing());
+
+    let (mut converter, result) = player.stop();
+    result.unwrap();
+    // out of range levels are saturated
+    let writes = mock.transactions();
+    assert_eq!(writes[0], Transaction::Write(vec![0x40, 255, 255]));
+
+    // the converter is given back
+    converter.analog_write_byte(7).unwrap();
+    assert_eq!(mock.dac(), 7);
+}